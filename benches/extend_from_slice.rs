@@ -0,0 +1,22 @@
+//! Appending a big slice at once against pushing its elements one by one.
+//! Run with `cargo bench --bench extend_from_slice`.
+use itreap::ITreap;
+use std::time::Instant;
+
+const SIZE: u64 = 10_000_000;
+
+fn main() {
+    let items: Vec<u64> = (0..SIZE).collect();
+
+    let start = Instant::now();
+    let mut pushed: ITreap<u64> = ITreap::new();
+    items.iter().for_each(|&e| pushed.push(e));
+    println!("push loop: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut extended: ITreap<u64> = ITreap::new();
+    extended.extend_from_slice(&items);
+    println!("extend_from_slice: {:?}", start.elapsed());
+
+    assert!(pushed == extended);
+}
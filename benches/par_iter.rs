@@ -0,0 +1,26 @@
+//! Parallel against sequential map and sum.
+//! Run with `cargo bench --bench par_iter --features rayon`.
+use itreap::ITreap;
+use rayon::prelude::*;
+use std::time::Instant;
+
+const SIZE: u64 = 10_000_000;
+
+fn main() {
+    let t: ITreap<u64> = (0..SIZE).collect();
+    let work = |e: &u64| (e * e) % 1_000_003;
+
+    let start = Instant::now();
+    let sequential: u64 = t.iter().map(work).sum();
+    println!("sequential: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let parallel: u64 = t.par_iter().map(work).sum();
+    println!(
+        "parallel ({} threads): {:?}",
+        rayon::current_num_threads(),
+        start.elapsed()
+    );
+
+    assert_eq!(sequential, parallel);
+}
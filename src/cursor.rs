@@ -0,0 +1,156 @@
+use super::{ITreap, Monoid};
+use alloc::vec::Vec;
+
+/// A position in an indexed treap, for efficient local edits.
+/// The block under the cursor is taken out of the tree so that moving and editing
+/// inside it costs no tree traversal. Elements before it stay in the treap
+/// and elements after it are kept in a separate treap until the cursor is dropped,
+/// which puts everything back together.
+///
+/// The cursor designates the element at its position, or nothing
+/// when it is past the last element.
+pub struct Cursor<'a, C, const B: usize, M: Monoid<C>> {
+    /// Holds all elements before the current block.
+    treap: &'a mut ITreap<C, B, M>,
+    block: Vec<C>,
+    /// Position inside the current block.
+    /// It is only at the end of the block when past the last element.
+    offset: usize,
+    suffix: ITreap<C, B, M>,
+}
+
+impl<C, const B: usize, M: Monoid<C>> ITreap<C, B, M> {
+    /// Returns a cursor at given index (`len` being past the last element).
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// let mut cursor = t.cursor_at(2);
+    /// assert_eq!(cursor.current(), Some(&2));
+    /// cursor.insert(10);
+    /// assert_eq!(cursor.remove(), Some(2));
+    /// drop(cursor);
+    /// assert!(t.iter().eq(&[0, 1, 10, 3, 4]));
+    /// ```
+    pub fn cursor_at(&mut self, index: usize) -> Cursor<'_, C, B, M> {
+        assert!(index <= self.len(), "cursor index out of bounds");
+        let suffix = self.split_off(index);
+        let block = self.pop_block_back();
+        let mut cursor = Cursor {
+            offset: block.len(),
+            block,
+            treap: self,
+            suffix,
+        };
+        cursor.settle();
+        cursor
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Cursor<'a, C, B, M> {
+    /// Returns the index of the cursor.
+    /// Cost is O(1).
+    pub fn index(&self) -> usize {
+        self.treap.len() + self.offset
+    }
+    /// Borrows the element at the cursor, if any.
+    /// Cost is O(1).
+    pub fn current(&self) -> Option<&C> {
+        self.block.get(self.offset)
+    }
+    /// Mutably borrows the element at the cursor, if any.
+    /// Summaries get updated when the cursor leaves the block.
+    /// Cost is O(1).
+    pub fn current_mut(&mut self) -> Option<&mut C> {
+        self.block.get_mut(self.offset)
+    }
+    /// Moves to the next element, returning `false` if already past the last one.
+    /// Cost is O(1) inside a block and O(log(n/B)+B) when changing blocks.
+    pub fn move_next(&mut self) -> bool {
+        if self.offset == self.block.len() {
+            return false;
+        }
+        self.offset += 1;
+        self.settle();
+        true
+    }
+    /// Moves to the previous element, returning `false` if already on the first one.
+    /// Cost is O(1) inside a block and O(log(n/B)+B) when changing blocks.
+    pub fn move_prev(&mut self) -> bool {
+        if self.offset == 0 {
+            if self.treap.is_empty() {
+                return false;
+            }
+            let mut previous = self.treap.pop_block_back();
+            self.offset = previous.len();
+            if previous.len() + self.block.len() <= B {
+                previous.append(&mut self.block);
+                self.block = previous;
+            } else {
+                let next = core::mem::replace(&mut self.block, previous);
+                self.suffix.push_block_front(next);
+            }
+        }
+        self.offset -= 1;
+        true
+    }
+    /// Inserts given element at the cursor, which then moves past it.
+    /// Cost is O(B).
+    pub fn insert(&mut self, element: C) {
+        if self.block.len() == B {
+            // make room by moving the half of the block we are not in back to a treap
+            let second_half = self.block.split_off(B / 2);
+            if self.offset < B / 2 {
+                self.suffix.push_block_front(second_half);
+            } else {
+                let first_half = core::mem::replace(&mut self.block, second_half);
+                self.treap.push_block_back(first_half);
+                self.offset -= B / 2;
+            }
+        }
+        self.block.insert(self.offset, element);
+        self.offset += 1;
+        self.settle();
+    }
+    /// Removes and returns the element at the cursor, which then designates the next one.
+    /// Returns `None` if past the last element.
+    /// Cost is O(B).
+    pub fn remove(&mut self) -> Option<C> {
+        if self.offset == self.block.len() {
+            return None;
+        }
+        let removed = self.block.remove(self.offset);
+        self.settle();
+        Some(removed)
+    }
+    /// Makes sure the element at the cursor (if any) is in the current block,
+    /// bringing in the next block if needed (fused with the current one if they fit).
+    fn settle(&mut self) {
+        if self.offset == self.block.len() && !self.suffix.is_empty() {
+            let mut next = self.suffix.pop_block_front();
+            if self.block.len() + next.len() <= B {
+                self.block.append(&mut next);
+            } else {
+                let previous = core::mem::replace(&mut self.block, next);
+                self.treap.push_block_back(previous);
+                self.offset = 0;
+            }
+        }
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Drop for Cursor<'a, C, B, M> {
+    /// Puts the current block and all following elements back into the treap.
+    fn drop(&mut self) {
+        self.treap.push_block_back(core::mem::take(&mut self.block));
+        self.treap.append(&mut self.suffix)
+    }
+}
@@ -0,0 +1,48 @@
+use super::{ITreap, Monoid, Node};
+use alloc::{string::String, vec};
+use core::fmt::Write;
+
+impl<C, const B: usize, M: Monoid<C>> ITreap<C, B, M> {
+    /// Returns a graphviz description of the tree structure.
+    /// Inner nodes are labeled with their priority, size and pending reversal,
+    /// leaves with their number of elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert!(t.to_dot().starts_with("digraph"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph itreap {\n");
+        let mut remaining_nodes = vec![(&self.root, 0)];
+        let mut next_id = 1;
+        while let Some((node, id)) = remaining_nodes.pop() {
+            match node {
+                Node::Inner(priority, size, _, reversed, children) => {
+                    writeln!(
+                        dot,
+                        "    n{} [label=\"p={}\\nsize={}{}\"];",
+                        id,
+                        priority,
+                        size,
+                        if *reversed { "\\nreversed" } else { "" }
+                    )
+                    .unwrap();
+                    for child in children {
+                        writeln!(dot, "    n{} -> n{};", id, next_id).unwrap();
+                        remaining_nodes.push((child, next_id));
+                        next_id += 1;
+                    }
+                }
+                Node::Leaf(block) => {
+                    writeln!(dot, "    n{} [shape=box,label=\"{}\"];", id, block.len()).unwrap()
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,22 @@
+use super::ITreap;
+
+/// Extra adapters for building indexed treaps out of iterators.
+pub trait IteratorExt: Iterator + Sized {
+    /// Collects all elements into an indexed treap.
+    /// This is just `collect` with a more explicit name.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::IteratorExt;
+    ///
+    /// let t = (0..5).map(|e| e * e).collect_treap();
+    /// assert!(t.iter().eq(&[0, 1, 4, 9, 16]));
+    /// ```
+    fn collect_treap(self) -> ITreap<Self::Item> {
+        self.collect()
+    }
+}
+
+impl<I: Iterator> IteratorExt for I {}
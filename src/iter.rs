@@ -0,0 +1,361 @@
+#[cfg(test)]
+use super::treap::STACK_ALLOCATIONS;
+use super::treap::{intersect_ranges, oriented_iter, OrientedBlock};
+use super::{ITreap, Monoid, Node, LEFT, RIGHT};
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+/// Nodes still to explore, with the range of indices they cover
+/// and whether they are below an odd number of pending reversals.
+type PendingNodes<'a, C, const B: usize, M> = Vec<(&'a Node<C, B, M>, Range<usize>, bool)>;
+
+/// An iterator on a range of elements of an indexed treap, in order.
+/// Both ends go down the tree independently,
+/// the count of remaining elements telling them when to stop.
+pub struct Iter<'a, C, const B: usize, M> {
+    selection: Range<usize>,
+    front_nodes: PendingNodes<'a, C, B, M>,
+    back_nodes: PendingNodes<'a, C, B, M>,
+    front_block: OrientedBlock<'a, C>,
+    back_block: OrientedBlock<'a, C>,
+    remaining: usize,
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Iter<'a, C, B, M> {
+    pub(super) fn new(root: &'a Node<C, B, M>, selection: Range<usize>) -> Self {
+        Iter::below(root, selection, false)
+    }
+    /// Loops on given range of the subtree rooted at `node`,
+    /// `reversed` telling if it lies below an odd number of pending reversals.
+    pub(super) fn below(node: &'a Node<C, B, M>, selection: Range<usize>, reversed: bool) -> Self {
+        let selection = intersect_ranges(&(0..node.len()), &selection);
+        // don't even allocate the stacks for empty selections
+        let (front_nodes, back_nodes) = if selection.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            #[cfg(test)]
+            STACK_ALLOCATIONS.with(|c| c.set(c.get() + 1));
+            (
+                vec![(node, 0..node.len(), reversed)],
+                vec![(node, 0..node.len(), reversed)],
+            )
+        };
+        Iter {
+            remaining: selection.len(),
+            selection,
+            front_nodes,
+            back_nodes,
+            front_block: oriented_iter(&[], false),
+            back_block: oriented_iter(&[], false),
+        }
+    }
+}
+
+/// Goes down to the next leaf intersecting the selection (the last one if `from_back`)
+/// and loops on its selected elements.
+fn next_block<'a, C, const B: usize, M: Monoid<C>>(
+    nodes: &mut PendingNodes<'a, C, B, M>,
+    selection: &Range<usize>,
+    from_back: bool,
+) -> Option<OrientedBlock<'a, C>> {
+    while let Some((node, node_range, reversed)) = nodes.pop() {
+        match node {
+            Node::Inner(_, _, _, node_reversed, children) => {
+                let reversed = reversed ^ node_reversed;
+                let [first, second] = if reversed {
+                    [&children[RIGHT], &children[LEFT]]
+                } else {
+                    [&children[LEFT], &children[RIGHT]]
+                };
+                let second_start = node_range.start + first.len();
+                let mut halves = [
+                    (second, second_start..node_range.end),
+                    (first, node_range.start..second_start),
+                ];
+                if from_back {
+                    halves.reverse()
+                }
+                for (child, child_range) in halves {
+                    if !intersect_ranges(&child_range, selection).is_empty() {
+                        nodes.push((child, child_range, reversed));
+                    }
+                }
+            }
+            Node::Leaf(block) => {
+                let selected = intersect_ranges(&node_range, selection);
+                let (start, end) = if reversed {
+                    (
+                        node_range.end - selected.end,
+                        node_range.end - selected.start,
+                    )
+                } else {
+                    (
+                        selected.start - node_range.start,
+                        selected.end - node_range.start,
+                    )
+                };
+                return Some(oriented_iter(&block[start..end], reversed));
+            }
+        }
+    }
+    None
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Iterator for Iter<'a, C, B, M> {
+    type Item = &'a C;
+    fn next(&mut self) -> Option<&'a C> {
+        while self.remaining > 0 {
+            if let Some(element) = self.front_block.next() {
+                self.remaining -= 1;
+                return Some(element);
+            }
+            self.front_block = next_block(&mut self.front_nodes, &self.selection, false)?;
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> DoubleEndedIterator for Iter<'a, C, B, M> {
+    fn next_back(&mut self) -> Option<&'a C> {
+        while self.remaining > 0 {
+            if let Some(element) = self.back_block.next_back() {
+                self.remaining -= 1;
+                return Some(element);
+            }
+            self.back_block = next_block(&mut self.back_nodes, &self.selection, true)?;
+        }
+        None
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> ExactSizeIterator for Iter<'a, C, B, M> {}
+
+impl<'a, C, const B: usize, M: Monoid<C>> IntoIterator for &'a ITreap<C, B, M> {
+    type Item = &'a C;
+    type IntoIter = Iter<'a, C, B, M>;
+    /// Loops on all elements, see `ITreap::iter`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..5).collect();
+    /// let mut sum = 0;
+    /// for e in &t {
+    ///     sum += e;
+    /// }
+    /// assert_eq!(sum, 10);
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A mutable iterator on a range of elements of an indexed treap, in order.
+pub struct IterMut<'a, C, const B: usize> {
+    selection: Range<usize>,
+    remaining_nodes: Vec<(&'a mut Node<C, B>, Range<usize>)>,
+    current_block: core::slice::IterMut<'a, C>,
+}
+
+impl<'a, C, const B: usize> IterMut<'a, C, B> {
+    pub(super) fn new(root: &'a mut Node<C, B>, selection: Range<usize>) -> Self {
+        let len = root.len();
+        let remaining_nodes = if intersect_ranges(&(0..len), &selection).is_empty() {
+            Vec::new()
+        } else {
+            vec![(root, 0..len)]
+        };
+        IterMut {
+            selection,
+            remaining_nodes,
+            current_block: [].iter_mut(),
+        }
+    }
+    /// Goes down to the next leaf intersecting the selection
+    /// and returns its selected elements.
+    fn next_block(&mut self) -> Option<&'a mut [C]> {
+        while let Some((node, node_range)) = self.remaining_nodes.pop() {
+            node.push_down();
+            match node {
+                Node::Inner(_, _, _, _, [left, right]) => {
+                    let right_start = node_range.start + left.len();
+                    let right_range = right_start..node_range.end;
+                    let left_range = node_range.start..right_start;
+                    if !intersect_ranges(&right_range, &self.selection).is_empty() {
+                        self.remaining_nodes.push((right, right_range));
+                    }
+                    if !intersect_ranges(&left_range, &self.selection).is_empty() {
+                        self.remaining_nodes.push((left, left_range));
+                    }
+                }
+                Node::Leaf(block) => {
+                    let selected = intersect_ranges(&node_range, &self.selection);
+                    let retained_elements =
+                        (selected.start - node_range.start)..(selected.end - node_range.start);
+                    return Some(&mut block[retained_elements]);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, C, const B: usize> Iterator for IterMut<'a, C, B> {
+    type Item = &'a mut C;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(element) = self.current_block.next() {
+                return Some(element);
+            }
+            self.current_block = self.next_block()?.iter_mut();
+        }
+    }
+}
+
+impl<'a, C, const B: usize> IntoIterator for &'a mut ITreap<C, B> {
+    type Item = &'a mut C;
+    type IntoIter = IterMut<'a, C, B>;
+    /// Mutably loops on all elements, see `ITreap::iter_mut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..4).collect();
+    /// for e in &mut t {
+    ///     *e *= 2;
+    /// }
+    /// assert!(t.iter().eq(&[0, 2, 4, 6]));
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An owning iterator on all elements of an indexed treap, in order.
+/// Blocks are moved out of the tree one at a time.
+pub struct IntoIter<C, const B: usize, M> {
+    remaining_nodes: Vec<Node<C, B, M>>,
+    current_block: alloc::vec::IntoIter<C>,
+    remaining: usize,
+}
+
+impl<C, const B: usize, M> Iterator for IntoIter<C, B, M> {
+    type Item = C;
+    fn next(&mut self) -> Option<C> {
+        loop {
+            if let Some(element) = self.current_block.next() {
+                self.remaining -= 1;
+                return Some(element);
+            }
+            let mut node = self.remaining_nodes.pop()?;
+            node.push_down();
+            match node {
+                Node::Inner(_, _, _, _, [left, right]) => {
+                    self.remaining_nodes.push(*right);
+                    self.remaining_nodes.push(*left);
+                }
+                Node::Leaf(block) => self.current_block = block.into_iter(),
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<C, const B: usize, M> ExactSizeIterator for IntoIter<C, B, M> {}
+
+impl<C, const B: usize, M> Drop for IntoIter<C, B, M> {
+    /// Dismantles remaining subtrees without recursion, see `Node::dismantle`.
+    fn drop(&mut self) {
+        self.remaining_nodes.drain(..).for_each(Node::dismantle)
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> IntoIterator for ITreap<C, B, M> {
+    type Item = C;
+    type IntoIter = IntoIter<C, B, M>;
+    /// Consumes the treap, looping on all its elements.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..5).collect();
+    /// let v: Vec<_> = t.into_iter().collect();
+    /// assert_eq!(v, vec![0, 1, 2, 3, 4]);
+    /// ```
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter {
+            remaining: self.root.len(),
+            remaining_nodes: vec![core::mem::replace(&mut self.root, Node::Leaf(Vec::new()))],
+            current_block: Vec::new().into_iter(),
+        }
+    }
+}
+
+/// A draining iterator on a range of elements of an indexed treap.
+/// Elements not yet yielded are dropped with the iterator,
+/// which then puts back together the remaining parts of the treap.
+pub struct Drain<'a, C, const B: usize, M: Monoid<C>> {
+    treap: &'a mut ITreap<C, B, M>,
+    elements: IntoIter<C, B, M>,
+    tail: ITreap<C, B, M>,
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Iterator for Drain<'a, C, B, M> {
+    type Item = C;
+    fn next(&mut self) -> Option<C> {
+        self.elements.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> ExactSizeIterator for Drain<'a, C, B, M> {}
+
+impl<'a, C, const B: usize, M: Monoid<C>> Drop for Drain<'a, C, B, M> {
+    fn drop(&mut self) {
+        self.treap.append(&mut self.tail)
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> ITreap<C, B, M> {
+    /// Removes all elements in given range, looping on them.
+    /// The range is removed even if the iterator is not fully consumed.
+    /// Cost is O(log(n/B)+B) plus O(1) per element yielded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or goes past the end.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..6).collect();
+    /// assert!(t.drain(1..4).eq(vec![1, 2, 3]));
+    /// assert!(t.iter().eq(&[0, 4, 5]));
+    /// ```
+    pub fn drain(&mut self, range: core::ops::Range<usize>) -> Drain<'_, C, B, M> {
+        assert!(range.start <= range.end, "drain range is decreasing");
+        let tail = self.split_off(range.end);
+        let middle = self.split_off(range.start);
+        Drain {
+            treap: self,
+            elements: middle.into_iter(),
+            tail,
+        }
+    }
+}
@@ -1,15 +1,1694 @@
+//! Indexed treaps : sequences with O(log(n)) positional access, insertion and removal.
+//!
+//! The crate is `no_std` compatible, only needing `alloc`.
+//! Disable the default `std` feature to use it without the standard library
+//! (`cargo build --no-default-features`). Without `std` there is no entropy
+//! source, so treaps built with `new` are seeded deterministically ; use
+//! `with_seed` to provide your own randomness.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
+
 mod node;
-pub(crate) use node::{Node, Priority, BLOCK_SIZE};
+pub(crate) use node::{oriented, Node, Priority, BLOCK_SIZE, LEFT, RIGHT};
+
+mod monoid;
+pub use monoid::{Monoid, Sum, TotalWeight, Weight};
 
 mod treap;
-pub use treap::ITreap;
+pub use treap::{ITreap, Snapshot, SumTreap, WeightedTreap};
+
+mod sum;
+
+mod weight;
+
+mod ext;
+pub use ext::IteratorExt;
+
+mod reversed;
+pub use reversed::ReversedView;
+
+mod iter;
+pub use iter::{Drain, IntoIter, Iter, IterMut};
+
+mod ring;
+pub use ring::RingTreap;
+
+mod cursor;
+pub use cursor::Cursor;
+
+mod persistent;
+pub use persistent::PersistentITreap;
+
+#[cfg(feature = "dot")]
+mod dot;
+
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+#[cfg(feature = "rayon")]
+mod rayon_impl;
+#[cfg(feature = "rayon")]
+pub use rayon_impl::ParIter;
 
 #[cfg(test)]
 mod tests {
-    use super::{ITreap, BLOCK_SIZE};
+    use super::{ITreap, Node, RingTreap, SumTreap, BLOCK_SIZE};
+    use itertools::Itertools;
+
+    /// Builds a perfectly balanced treap of `2^height` leaves holding `leaf_size` elements each.
+    fn fragmented(height: u32, leaf_size: usize) -> ITreap<usize> {
+        fn build(height: u32, start: usize, leaf_size: usize) -> Node<usize> {
+            if height == 0 {
+                Node::Leaf((start..start + leaf_size).collect())
+            } else {
+                let half = leaf_size << (height - 1);
+                let left = build(height - 1, start, leaf_size);
+                let right = build(height - 1, start + half, leaf_size);
+                Node::inner(u64::from(height), [Box::new(left), Box::new(right)])
+            }
+        }
+        ITreap::from_root(build(height, 0, leaf_size))
+    }
+
     #[test]
     fn collect() {
         let r = 0..10 * BLOCK_SIZE;
         assert!(r.clone().collect::<ITreap<_>>().iter().copied().eq(r));
     }
+    #[test]
+    fn block_equality() {
+        let r = 0..10 * BLOCK_SIZE;
+        let t1: ITreap<_> = r.clone().collect();
+        let t2: ITreap<_> = r.clone().collect();
+        let before = super::treap::BLOCK_COMPARISONS.with(|c| c.get());
+        assert!(t1 == t2);
+        let after = super::treap::BLOCK_COMPARISONS.with(|c| c.get());
+        assert_eq!(after, before + 1);
+        let mut t3 = ITreap::new();
+        for e in r {
+            t3.push(e);
+        }
+        assert!(t1 == t3);
+        t3[5] = 0;
+        assert!(t1 != t3);
+    }
+    #[test]
+    fn cut() {
+        let mut t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        let range = 2 * BLOCK_SIZE + 7..5 * BLOCK_SIZE + 3;
+        let mut middle = t.cut(range.clone());
+        assert!(t.is_valid());
+        assert!(middle.is_valid());
+        assert!(middle.iter().copied().eq(range.clone()));
+        assert!(t
+            .iter()
+            .copied()
+            .eq((0..range.start).chain(range.end..10 * BLOCK_SIZE)));
+        middle.insert(0, 0);
+        middle.push(0);
+        let mut end = t.split_off(range.start);
+        t.append(&mut middle);
+        t.append(&mut end);
+        assert!(t.is_valid());
+        assert!(middle.is_empty());
+        assert!(t.iter().copied().eq((0..range.start)
+            .chain(std::iter::once(0))
+            .chain(range.clone())
+            .chain(std::iter::once(0))
+            .chain(range.end..10 * BLOCK_SIZE)));
+    }
+    #[test]
+    fn balance() {
+        let t: ITreap<_> = (0..100 * BLOCK_SIZE).collect();
+        assert!(t.is_balanced(1.5));
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        let mut rng = SmallRng::seed_from_u64(204);
+        let mut t: ITreap<_> = ITreap::with_seed(204);
+        for i in 0..20 * BLOCK_SIZE {
+            t.insert(rng.gen_range(0..=i), i);
+        }
+        assert!(t.is_balanced(4.0));
+    }
+    #[test]
+    fn coalesce() {
+        let mut t = fragmented(6, BLOCK_SIZE / 3);
+        assert!(t.is_valid());
+        assert_eq!(t.root.leaf_count(), 64);
+        t.coalesce();
+        assert!(t.is_valid());
+        assert_eq!(t.root.leaf_count(), 32);
+        assert!(t.iter().copied().eq(0..64 * (BLOCK_SIZE / 3)));
+    }
+    #[test]
+    fn get_or_default() {
+        let t: ITreap<_> = (1..=100).collect();
+        assert!((0..100).all(|i| t.get_or_default(i) == i + 1));
+        assert_eq!(t.get_or_default(100), 0);
+        assert_eq!(t.get_or_default(1000), 0);
+        assert_eq!(ITreap::<u32>::new().get_or_default(0), 0);
+    }
+    #[test]
+    fn push_slice() {
+        let mut t: ITreap<u32> = ITreap::from_iter_with_seed(0..1234, 207);
+        let slice: Vec<u32> = (1234..1234 + 3 * BLOCK_SIZE as u32).collect();
+        t.push_slice(&slice);
+        assert!(t.is_valid());
+        assert!(t.is_balanced(2.0));
+        // half blocks for the collected part, full blocks for the slice
+        assert_eq!(t.leaf_count(), 1234usize.div_ceil(BLOCK_SIZE / 2) + 3);
+        assert!(t.iter().copied().eq(0..1234 + 3 * BLOCK_SIZE as u32));
+    }
+    #[test]
+    fn merge_insert_sorted() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 3).collect();
+        let batch: Vec<_> = (0..2 * BLOCK_SIZE).map(|e| e * 7 + 1).collect();
+        t.merge_insert_sorted(batch);
+        assert!(t.is_valid());
+        assert_eq!(t.len(), 7 * BLOCK_SIZE);
+        assert!(t.iter().tuple_windows().all(|(a, b)| a <= b));
+    }
+    #[test]
+    fn rechunk() {
+        let t: ITreap<i32, 8> = (0..1000).collect();
+        let t2: ITreap<i32, 64> = t.rechunk();
+        assert!(t2.is_valid());
+        assert!(t2.iter().copied().eq(0..1000));
+        assert!(t2.oriented_chunks().all(|(b, _)| b.len() <= 64));
+        assert!(t2.oriented_chunks().count() <= 2 * 1000 / 64 + 1);
+    }
+    #[test]
+    fn shuffle() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let mut t: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        let mut t2: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        t.shuffle(&mut StdRng::seed_from_u64(42));
+        t2.shuffle(&mut StdRng::seed_from_u64(42));
+        assert!(t.is_valid());
+        assert!(t == t2);
+        assert!(!t.iter().copied().eq(0..3 * BLOCK_SIZE));
+        assert!(t.iter().copied().sorted().eq(0..3 * BLOCK_SIZE));
+    }
+    #[test]
+    fn covers() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 2).collect();
+        assert!(t.covers(&0, &0));
+        assert!(t.covers(&3, &4));
+        assert!(t.covers(&(2 * BLOCK_SIZE - 1), &(2 * BLOCK_SIZE)));
+        assert!(t.covers(&0, &(20 * BLOCK_SIZE)));
+        assert!(!t.covers(&3, &3));
+        assert!(!t.covers(&(10 * BLOCK_SIZE), &(20 * BLOCK_SIZE)));
+        assert!(!ITreap::new().covers(&0, &10));
+    }
+    #[test]
+    fn swap_remove() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let mut v: Vec<_> = (0..5 * BLOCK_SIZE).collect();
+        for &index in &[3 * BLOCK_SIZE, BLOCK_SIZE / 2, 0, 4 * BLOCK_SIZE] {
+            assert_eq!(t.swap_remove(index), v.swap_remove(index));
+        }
+        assert!(t.is_valid());
+        assert!(t.iter().eq(v.iter()));
+    }
+    #[test]
+    fn swap_remove_front() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        assert_eq!(t.swap_remove_front(3 * BLOCK_SIZE), 3 * BLOCK_SIZE);
+        assert_eq!(t[3 * BLOCK_SIZE - 1], 0);
+        assert_eq!(t[0], 1);
+        assert_eq!(t.len(), 5 * BLOCK_SIZE - 1);
+        assert_eq!(t.swap_remove_front(0), 1);
+        assert_eq!(t[0], 2);
+        assert!(t.is_valid());
+    }
+    #[test]
+    #[cfg(feature = "std")]
+    fn frequencies() {
+        let t: ITreap<_> = (0..10 * BLOCK_SIZE).map(|e| e % 7).collect();
+        let frequencies = t.frequencies();
+        assert_eq!(frequencies.len(), 7);
+        for (value, count) in frequencies {
+            assert_eq!(count, t.iter().filter(|&&e| e == value).count());
+        }
+    }
+    #[test]
+    fn iter_with_block() {
+        let t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        assert!(t
+            .iter_with_block()
+            .tuple_windows()
+            .all(|((b1, _), (b2, _))| b1 == b2 || b1 + 1 == b2));
+        let chunks = t.oriented_chunks().collect::<Vec<_>>();
+        assert!(t
+            .iter_with_block()
+            .all(|(block, e)| chunks[block].0.contains(e)));
+        assert!(t.iter_with_block().map(|(_, e)| e).eq(t.iter()));
+    }
+    #[test]
+    fn empty_between() {
+        let t: ITreap<_> = (0..10).collect();
+        let before = super::treap::STACK_ALLOCATIONS.with(|c| c.get());
+        assert_eq!(t.between(5..5).count(), 0);
+        assert_eq!(t.between(100..200).count(), 0);
+        let after = super::treap::STACK_ALLOCATIONS.with(|c| c.get());
+        assert_eq!(before, after);
+        assert!(t.between(5..100).eq(&[5, 6, 7, 8, 9]));
+    }
+    #[test]
+    fn validate() {
+        let mut t: ITreap<_> = (0..4 * BLOCK_SIZE).collect();
+        assert_eq!(t.validate(), Ok(()));
+        if let Node::Inner(_, size, _, _, _) = &mut t.root {
+            *size += 1;
+        }
+        assert_eq!(
+            t.validate(),
+            Err(format!(
+                "size mismatch at root: recorded {} but children hold {}",
+                4 * BLOCK_SIZE + 1,
+                4 * BLOCK_SIZE
+            ))
+        );
+        let mut t = fragmented(2, 10);
+        if let Node::Inner(_, _, _, _, children) = &mut t.root {
+            if let Node::Inner(priority, _, _, _, _) = &mut *children[1] {
+                *priority = 5;
+            }
+        }
+        assert_eq!(
+            t.validate(),
+            Err("heap order violated at depth 1 (path R): priority 5 above father's 2".to_owned())
+        );
+        let mut t = fragmented(2, 10);
+        if let Node::Inner(_, _, _, _, children) = &mut t.root {
+            if let Node::Inner(_, size, _, _, grand_children) = &mut *children[0] {
+                *size -= 10;
+                *grand_children[1] = Node::Leaf(Vec::new());
+            }
+            if let Node::Inner(_, size, _, _, _) = &mut t.root {
+                *size -= 10;
+            }
+        }
+        assert_eq!(
+            t.validate(),
+            Err("empty non-root leaf at depth 2 (path LR)".to_owned())
+        );
+    }
+    #[test]
+    fn from_runs() {
+        let runs = vec![(3 * BLOCK_SIZE / 2, 1), (0, 2), (7, 3), (2 * BLOCK_SIZE, 4)];
+        let t: ITreap<_> = ITreap::from_runs(runs.clone());
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(runs
+            .into_iter()
+            .flat_map(|(count, value)| std::iter::repeat_n(value, count))));
+        assert!(ITreap::<u8>::from_runs(vec![(0, 1)]).is_empty());
+    }
+    #[test]
+    fn peek() {
+        let mut t: ITreap<usize> = ITreap::new();
+        assert_eq!(t.peek_front(), None);
+        assert_eq!(t.peek_back(), None);
+        assert_eq!(t.peek_front_mut(), None);
+        assert_eq!(t.peek_back_mut(), None);
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        assert_eq!(t.peek_front(), Some(&0));
+        assert_eq!(t.peek_back(), Some(&(5 * BLOCK_SIZE - 1)));
+        *t.peek_front_mut().unwrap() = 10;
+        *t.peek_back_mut().unwrap() = 20;
+        assert_eq!(t.peek_front(), Some(&10));
+        assert_eq!(t.peek_back(), Some(&20));
+    }
+    #[test]
+    fn scan_map() {
+        let t: ITreap<i64> = (0..3 * BLOCK_SIZE as i64).map(|e| e * e).collect();
+        let differences = t.scan_map(0, |previous, &e| {
+            let difference = e - *previous;
+            *previous = e;
+            difference
+        });
+        assert!(differences.is_valid());
+        assert!(differences
+            .iter()
+            .copied()
+            .eq((0..3 * BLOCK_SIZE as i64).map(|e| if e == 0 { 0 } else { 2 * e - 1 })));
+    }
+    #[test]
+    fn at() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 2).collect();
+        for &i in &[0, 1, BLOCK_SIZE / 2, 3 * BLOCK_SIZE, 5 * BLOCK_SIZE - 1] {
+            assert_eq!(t.at(i), t[i]);
+            assert_eq!(t.at(i), 2 * i);
+        }
+    }
+    #[test]
+    fn scatter() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let updates: Vec<_> = (BLOCK_SIZE..2 * BLOCK_SIZE + 10)
+            .step_by(3)
+            .map(|i| (i, 0))
+            .collect();
+        let rejected = t.scatter(updates.iter().cloned().chain(vec![(5 * BLOCK_SIZE, 1)]));
+        assert_eq!(rejected, vec![(5 * BLOCK_SIZE, 1)]);
+        assert!(t.iter().enumerate().all(|(i, &e)| {
+            if updates.iter().any(|&(index, _)| index == i) {
+                e == 0
+            } else {
+                e == i
+            }
+        }));
+    }
+    #[test]
+    fn longest_sorted_run() {
+        let t: ITreap<_> = (0..BLOCK_SIZE)
+            .rev()
+            .chain(0..3 * BLOCK_SIZE)
+            .chain(0..2 * BLOCK_SIZE)
+            .collect();
+        assert_eq!(t.longest_sorted_run(), BLOCK_SIZE - 1..4 * BLOCK_SIZE);
+        assert_eq!(ITreap::<u32>::new().longest_sorted_run(), 0..0);
+    }
+    #[test]
+    fn into_sorted_vec() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.shuffle(&mut rand::thread_rng());
+        assert_eq!(t.into_sorted_vec(), (0..5 * BLOCK_SIZE).collect::<Vec<_>>());
+    }
+    #[test]
+    fn batch_search() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 3).collect();
+        let keys: Vec<_> = (0..16 * BLOCK_SIZE).step_by(7).collect();
+        let results = t.batch_search(&keys);
+        assert_eq!(results.len(), keys.len());
+        assert!(keys
+            .iter()
+            .zip(results)
+            .all(|(key, result)| t.binary_search(key) == result));
+        let v: Vec<_> = t.iter().copied().collect();
+        assert!(keys
+            .iter()
+            .all(|key| t.binary_search(key) == v.binary_search(key)));
+    }
+    #[test]
+    fn zip_mut() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let other: ITreap<_, 64> = (0..3 * BLOCK_SIZE).map(|e| 2 * e).collect();
+        t.zip_mut(&other, |e, o| *e += o);
+        assert!(t.iter().copied().eq((0..3 * BLOCK_SIZE)
+            .map(|e| 3 * e)
+            .chain(3 * BLOCK_SIZE..5 * BLOCK_SIZE)));
+    }
+    #[test]
+    fn ring() {
+        let capacity = 2 * BLOCK_SIZE + 3;
+        let mut r: RingTreap<_> = RingTreap::new(capacity);
+        assert_eq!(r.capacity(), capacity);
+        for i in 0..capacity {
+            assert_eq!(r.push(i), None);
+        }
+        for i in capacity..5 * BLOCK_SIZE {
+            assert_eq!(r.push(i), Some(i - capacity));
+        }
+        assert_eq!(r.len(), capacity);
+        assert!(r.as_treap().is_valid());
+        assert!(r
+            .iter()
+            .copied()
+            .eq(5 * BLOCK_SIZE - capacity..5 * BLOCK_SIZE));
+        let mut r: RingTreap<_> = RingTreap::new(0);
+        assert_eq!(r.push(1), Some(1));
+        assert!(r.is_empty());
+        let mut r: RingTreap<_, 4> = RingTreap::new(10);
+        for i in 0..30 {
+            r.push(i);
+        }
+        assert!(r.as_treap().is_valid());
+        assert!(r.iter().copied().eq(20..30));
+    }
+    #[test]
+    fn sample_weighted() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        let draws = 10_000;
+        let mut counts = [0; 5];
+        for _ in 0..draws {
+            let mut t: SumTreap<u32> = (1..=4).collect();
+            counts[t.sample_weighted(&mut rng).unwrap() as usize] += 1;
+        }
+        for (weight, count) in counts.iter().enumerate().skip(1) {
+            let frequency = *count as f64 / draws as f64;
+            assert!((frequency - weight as f64 / 10.0).abs() < 0.02);
+        }
+        let mut t: SumTreap<u64> = (0..3 * BLOCK_SIZE as u64).map(|e| e % 10 + 1).collect();
+        for remaining in (0..t.len()).rev() {
+            assert!(t.sample_weighted(&mut rng).is_some());
+            assert_eq!(t.len(), remaining);
+            assert_eq!(t.sum(), t.iter().sum::<u64>());
+        }
+        assert!(t.is_valid());
+        assert_eq!(t.sample_weighted(&mut rng), None);
+    }
+    #[test]
+    fn priority_collisions() {
+        let t: ITreap<_> = ITreap::from_iter_with_seed(0..100 * BLOCK_SIZE, 228);
+        assert_eq!(t.priority_collisions(), 0);
+        // a degenerate generator handing out the same priority for all nodes
+        let mut constant = rand::rngs::mock::StepRng::new(7, 0);
+        let mut t: ITreap<_> = ITreap::with_seed(228);
+        for i in 0..20 * BLOCK_SIZE {
+            t.root.insert(i / 2, i, &mut constant);
+        }
+        assert!(t.is_valid());
+        assert!(t.priority_collisions() > 0);
+        assert_eq!(t.priority_collisions(), t.leaf_count() - 2);
+    }
+    #[test]
+    fn try_for_each() {
+        let t: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        let stop = BLOCK_SIZE + 7;
+        let mut visited = 0;
+        let r = t.try_for_each(|&e| {
+            visited += 1;
+            if e == stop {
+                Err(e)
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(r, Err(stop));
+        assert_eq!(visited, stop + 1);
+        assert_eq!(t.try_for_each(|_| Ok::<_, ()>(())), Ok(()));
+    }
+    #[test]
+    fn nearest() {
+        let t: ITreap<_> = (0..3 * BLOCK_SIZE as i64).map(|e| 10 * e + 10).collect();
+        assert_eq!(t.nearest(&0), Some((0, &10)));
+        assert_eq!(t.nearest(&12_341), Some((1233, &12_340)));
+        assert_eq!(t.nearest(&12_349), Some((1233, &12_340)));
+        assert_eq!(t.nearest(&12_350), Some((1234, &12_350)));
+        let last = t.len() - 1;
+        assert_eq!(t.nearest(&1_000_000), Some((last, t.last().unwrap())));
+        assert_eq!(ITreap::<i64>::new().nearest(&3), None);
+        let t: ITreap<u32> = vec![3, 7].into_iter().collect();
+        assert_eq!(t.nearest(&0), Some((0, &3)));
+        let t: ITreap<String> = ["b", "d"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(t.nearest(&"a".to_string()), Some((0, &"b".to_string())));
+        assert_eq!(t.nearest(&"c".to_string()), Some((0, &"b".to_string())));
+        assert_eq!(t.nearest(&"d".to_string()), Some((1, &"d".to_string())));
+        assert_eq!(t.nearest(&"e".to_string()), Some((1, &"d".to_string())));
+    }
+    #[test]
+    fn replace_all_from_vec() {
+        let mut t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        let allocations: std::collections::HashSet<_> = t.chunks().map(|b| b.as_ptr()).collect();
+        let boxes: std::collections::HashSet<_> = t.node_addresses().into_iter().collect();
+        for size in [10 * BLOCK_SIZE, 9 * BLOCK_SIZE, 7 * BLOCK_SIZE] {
+            t.replace_all_from_vec((0..size).rev().collect());
+            assert!(t.is_valid());
+            assert!(t.iter().copied().eq((0..size).rev()));
+            assert!(t.chunks().all(|b| allocations.contains(&b.as_ptr())));
+            assert!(t.node_addresses().iter().all(|n| boxes.contains(n)));
+        }
+        t.replace_all_from_vec((0..100 * BLOCK_SIZE).collect());
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..100 * BLOCK_SIZE));
+    }
+    #[test]
+    fn from_iter_unchecked() {
+        let r = 0..10 * BLOCK_SIZE + 17;
+        let checked: ITreap<_> = r.clone().collect();
+        let unchecked = ITreap::from_iter_unchecked(r);
+        assert!(unchecked.is_valid());
+        assert!(checked
+            .oriented_chunks()
+            .map(|(b, _)| b.len())
+            .eq(unchecked.oriented_chunks().map(|(b, _)| b.len())));
+        assert!(checked == unchecked);
+    }
+    #[test]
+    fn split_at_predicate() {
+        let mut t: ITreap<_> = (0..10 * BLOCK_SIZE).map(|e| 2 * e).collect();
+        let threshold = 5 * BLOCK_SIZE + 1;
+        let big = t.split_at_predicate(|&e| e < threshold);
+        assert!(t.is_valid() && big.is_valid());
+        assert!(t.iter().copied().eq((0..threshold).step_by(2)));
+        assert!(big
+            .iter()
+            .copied()
+            .eq((threshold + 1..20 * BLOCK_SIZE).step_by(2)));
+        assert!(t.split_at_predicate(|_| true).is_empty());
+    }
+    #[test]
+    fn try_map() {
+        let strings = || (0..3 * BLOCK_SIZE).map(|e| e.to_string());
+        let t: ITreap<_> = strings().collect();
+        let numbers = t.try_map(|s| s.parse::<usize>()).unwrap();
+        assert!(numbers.is_valid());
+        assert!(numbers.iter().copied().eq(0..3 * BLOCK_SIZE));
+        let mut broken: ITreap<_> = strings().collect();
+        broken[BLOCK_SIZE + 3] = "oops".to_string();
+        assert!(broken.try_map(|s| s.parse::<usize>()).is_err());
+    }
+    #[test]
+    fn count_less_than() {
+        let v: Vec<u32> = (0..5 * BLOCK_SIZE)
+            .map(|_| rand::random::<u32>() % 1000)
+            .collect();
+        let t: ITreap<_> = v.iter().copied().collect();
+        for x in [0, 1, 500, 999, 1000] {
+            assert_eq!(t.count_less_than(&x), v.iter().filter(|&&e| e < x).count());
+        }
+    }
+    #[test]
+    fn block_at() {
+        let mut t = fragmented(3, 10);
+        let blocks = t.root.leaf_count();
+        let mut elements = Vec::new();
+        for i in 0..blocks {
+            elements.extend_from_slice(t.block_at(i).unwrap());
+        }
+        assert!(elements.into_iter().eq(0..80));
+        assert_eq!(t.block_at(blocks), None);
+        t.reverse();
+        t.reverse_range(5..47);
+        let expected: Vec<_> = t.iter().copied().collect();
+        let blocks = t.leaf_count();
+        let mut elements = Vec::new();
+        for i in 0..blocks {
+            elements.extend_from_slice(t.block_at(i).unwrap());
+        }
+        assert_eq!(elements, expected);
+        assert_eq!(t.block_at(blocks), None);
+    }
+    #[test]
+    fn try_append() {
+        let mut t: ITreap<_> = (0..2 * BLOCK_SIZE).collect();
+        let mut other: ITreap<_> = (2 * BLOCK_SIZE..5 * BLOCK_SIZE).collect();
+        assert_eq!(t.try_append(&mut other, 5 * BLOCK_SIZE - 1), Err(()));
+        assert!(t.iter().copied().eq(0..2 * BLOCK_SIZE));
+        assert!(other.iter().copied().eq(2 * BLOCK_SIZE..5 * BLOCK_SIZE));
+        assert_eq!(t.try_append(&mut other, 5 * BLOCK_SIZE), Ok(()));
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..5 * BLOCK_SIZE));
+        assert!(other.is_empty());
+    }
+    #[test]
+    fn rolling() {
+        let v: Vec<u64> = (0..3 * BLOCK_SIZE as u64).map(|e| e * e % 17).collect();
+        let t: ITreap<_> = v.iter().copied().collect();
+        let sums = t.rolling(3, |w| w.iter().copied().sum::<u64>());
+        assert_eq!(sums.len(), v.len() - 2);
+        assert!(sums
+            .iter()
+            .copied()
+            .eq(v.windows(3).map(|w| w.iter().sum::<u64>())));
+        assert!(t.rolling(v.len() + 1, |w| w.len()).is_empty());
+    }
+    #[test]
+    fn partition_in_place() {
+        let mut t: ITreap<_> = (0..3 * BLOCK_SIZE).rev().collect();
+        let split = t.partition_in_place(|e| e % 2 == 0);
+        assert_eq!(split, 3 * BLOCK_SIZE / 2);
+        assert!(t.is_valid());
+        assert!(t
+            .iter()
+            .copied()
+            .eq((0..3 * BLOCK_SIZE).rev().sorted_by_key(|e| e % 2)));
+    }
+    #[test]
+    fn partition() {
+        let t: ITreap<_> = (0..40 * BLOCK_SIZE).collect();
+        let (even, odd) = t.partition(|e| e % 2 == 0);
+        assert!(even.is_valid() && odd.is_valid());
+        assert!(even.is_balanced(1.5) && odd.is_balanced(1.5));
+        assert!(even.iter().copied().eq((0..40 * BLOCK_SIZE).step_by(2)));
+        assert!(odd.iter().copied().eq((1..40 * BLOCK_SIZE).step_by(2)));
+    }
+    #[test]
+    fn iter_map() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| (e, e * 3)).collect();
+        assert!(t.iter_map(|(_, y)| *y).eq(t.iter().map(|(_, y)| *y)));
+    }
+    #[test]
+    fn check_heap_order() {
+        let mut t = fragmented(3, 10);
+        assert!(t.check_heap_order());
+        if let Node::Inner(_, _, _, _, children) = &mut t.root {
+            if let Node::Inner(priority, _, _, _, _) = children[1].as_mut() {
+                *priority = 10;
+            }
+        }
+        assert!(!t.check_heap_order());
+        assert!(t.validate().unwrap_err().starts_with("heap order violated"));
+    }
+    #[test]
+    fn reversed() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 2).collect();
+        assert!(t
+            .reversed()
+            .copied()
+            .eq((0..5 * BLOCK_SIZE).rev().map(|e| e * 2)));
+        let view = t.reversed_view();
+        assert_eq!(view.len(), t.len());
+        assert!((0..t.len()).all(|i| view[i] == t[t.len() - 1 - i]));
+        assert!(view.iter().eq(t.reversed()));
+    }
+    #[test]
+    fn extend_from_treap() {
+        let mut t: ITreap<_> = (0..2 * BLOCK_SIZE + 5).collect();
+        let other: ITreap<_> = (2 * BLOCK_SIZE + 5..6 * BLOCK_SIZE).collect();
+        t.extend_from_treap(&other);
+        t.extend_from_treap(&ITreap::new());
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..6 * BLOCK_SIZE));
+        assert!(other.iter().copied().eq(2 * BLOCK_SIZE + 5..6 * BLOCK_SIZE));
+    }
+    #[test]
+    fn change_points() {
+        let v: Vec<_> = (0..5 * BLOCK_SIZE).map(|e| e / 333).collect();
+        let t: ITreap<_> = v.iter().copied().collect();
+        let expected: Vec<_> = (0..v.len())
+            .filter(|&i| i == 0 || v[i] != v[i - 1])
+            .collect();
+        assert_eq!(t.change_points(), expected);
+        assert!(ITreap::<u8>::new().change_points().is_empty());
+    }
+    #[test]
+    fn from_sorted_dedup() {
+        let t: ITreap<_> = ITreap::from_sorted_dedup((0..6 * BLOCK_SIZE).map(|e| e / 3));
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..2 * BLOCK_SIZE));
+    }
+    #[test]
+    fn iter_from() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        for k in [0, 1, BLOCK_SIZE - 1, 3 * BLOCK_SIZE + 7, 5 * BLOCK_SIZE] {
+            assert!(t.iter_from(k).eq(t.iter().skip(k)));
+        }
+    }
+    #[test]
+    fn median_by_key() {
+        let records: ITreap<_> = (0..3 * BLOCK_SIZE + 1)
+            .map(|e| (e * 7919 % (3 * BLOCK_SIZE + 1), e))
+            .collect();
+        let median = records.median_by_key(|r| r.0).unwrap();
+        assert_eq!(median.0, 3 * BLOCK_SIZE / 2);
+        assert_eq!(
+            records.iter().filter(|r| r.0 < median.0).count(),
+            3 * BLOCK_SIZE / 2
+        );
+        assert_eq!(ITreap::<u8>::new().median_by_key(|&e| e), None);
+    }
+    #[test]
+    fn snapshot() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let snapshot = t.snapshot();
+        t.insert(3, 0);
+        t.swap_remove(2 * BLOCK_SIZE);
+        let mut end = t.split_off(BLOCK_SIZE);
+        end.push(1);
+        t.append(&mut end);
+        t.restore(snapshot);
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..5 * BLOCK_SIZE));
+    }
+    #[test]
+    fn remove() {
+        let mut t: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        let mut v: Vec<_> = (0..3 * BLOCK_SIZE).collect();
+        for &index in &[3 * BLOCK_SIZE / 2, 0, 3 * BLOCK_SIZE - 3, BLOCK_SIZE, 17] {
+            assert_eq!(t.remove(index), v.remove(index));
+            assert!(t.is_valid());
+        }
+        assert!(t.iter().eq(v.iter()));
+        while !t.is_empty() {
+            assert_eq!(t.remove(t.len() / 2), v.remove(v.len() / 2));
+        }
+        assert!(t.is_valid());
+    }
+    #[test]
+    #[should_panic(expected = "removal index (is 3) should be < len (is 3)")]
+    fn remove_out_of_bounds() {
+        let mut t: ITreap<_> = (0..3).collect();
+        t.remove(3);
+    }
+    #[test]
+    fn merge_k_sorted() {
+        let treaps: Vec<ITreap<_>> = (1..6)
+            .map(|k| (0..k * BLOCK_SIZE).map(|e| e * k).collect())
+            .chain(std::iter::once(ITreap::new()))
+            .collect();
+        let total = treaps.iter().map(|t| t.len()).sum::<usize>();
+        let merged = ITreap::merge_k_sorted(treaps);
+        assert!(merged.is_valid());
+        assert_eq!(merged.len(), total);
+        assert!(merged.iter().tuple_windows().all(|(a, b)| a <= b));
+    }
+    #[test]
+    fn pop() {
+        let mut t = ITreap::new();
+        assert_eq!(t.pop(), None);
+        assert_eq!(t.pop_front(), None);
+        for e in 0..3 * BLOCK_SIZE {
+            t.push(e);
+        }
+        for e in (BLOCK_SIZE..3 * BLOCK_SIZE).rev() {
+            assert_eq!(t.pop(), Some(e));
+        }
+        assert!(t.is_valid());
+        for e in 0..BLOCK_SIZE {
+            assert_eq!(t.pop_front(), Some(e));
+        }
+        assert_eq!(t.pop(), None);
+        assert!(t.is_valid());
+    }
+    #[test]
+    fn find_map_indexed() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).map(|e| e * 3).collect();
+        let target = 3 * (2 * BLOCK_SIZE + 11);
+        let found = t.find_map_indexed(|i, &e| if e >= target { Some((i, e)) } else { None });
+        assert_eq!(
+            found,
+            Some((2 * BLOCK_SIZE + 11, (2 * BLOCK_SIZE + 11, target)))
+        );
+        assert_eq!(
+            t.find_map_indexed(|_, &e| if e == 1 { Some(()) } else { None }),
+            None
+        );
+    }
+    #[test]
+    fn split_off() {
+        let n = 4 * BLOCK_SIZE + 123;
+        for &index in &[
+            0,
+            1,
+            BLOCK_SIZE / 2,
+            BLOCK_SIZE / 2 + 3,
+            2 * BLOCK_SIZE,
+            n - 1,
+            n,
+        ] {
+            let mut t: ITreap<_> = (0..n).collect();
+            let end = t.split_off(index);
+            assert!(t.is_valid() && end.is_valid());
+            assert!(t.iter().copied().eq(0..index));
+            assert!(end.iter().copied().eq(index..n));
+        }
+    }
+    #[test]
+    #[should_panic(expected = "split index out of bounds")]
+    fn split_off_out_of_bounds() {
+        let mut t: ITreap<_> = (0..3).collect();
+        t.split_off(4);
+    }
+    #[test]
+    fn append() {
+        for &(n1, n2) in &[
+            (0, 1234),
+            (1234, 0),
+            (7, 3),
+            (BLOCK_SIZE + 321, 2 * BLOCK_SIZE + 77),
+        ] {
+            let mut t: ITreap<_> = (0..n1).collect();
+            let mut other: ITreap<_> = (n1..n1 + n2).collect();
+            t.append(&mut other);
+            assert!(t.is_valid());
+            assert!(other.is_empty());
+            assert!(t.iter().copied().eq(0..n1 + n2));
+        }
+        let mut t = ITreap::new();
+        for i in 0..100 {
+            let mut piece: ITreap<_> = (i * 97..(i + 1) * 97).collect();
+            t.append(&mut piece);
+        }
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..9700));
+    }
+    #[test]
+    fn index_at_boundaries() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let mut v: Vec<_> = (0..5 * BLOCK_SIZE).collect();
+        assert!((0..v.len()).all(|i| t[i] == v[i]));
+        for i in 0..v.len() {
+            t[i] *= 2;
+            v[i] *= 2;
+        }
+        assert!(t.iter().eq(v.iter()));
+        let t = fragmented(4, 3);
+        assert!((0..t.len()).all(|i| t[i] == i));
+        assert_eq!(t.root.get(t.len()), None);
+    }
+    #[test]
+    fn tiny_blocks() {
+        fn churn<const B: usize>() {
+            use rand::{rngs::StdRng, Rng, SeedableRng};
+            let mut rng = StdRng::seed_from_u64(B as u64);
+            let mut t: ITreap<usize, B> = (0..10).collect();
+            let mut v: Vec<usize> = (0..10).collect();
+            for i in 0..500 {
+                let index = rng.gen_range(0..=v.len());
+                t.insert(index, i);
+                v.insert(index, i);
+                if i % 3 == 0 {
+                    let index = rng.gen_range(0..v.len());
+                    assert_eq!(t.remove(index), v.remove(index));
+                }
+                assert!(t.is_valid());
+            }
+            assert!(t.chunks().all(|b| !b.is_empty() && b.len() <= B));
+            assert!((0..v.len()).all(|i| t[i] == v[i]));
+            t.extend_from_slice(&v);
+            t.push_slice(&v);
+            t.extend(v.iter().copied());
+            let runs: ITreap<usize, B> = ITreap::from_runs(vec![(3, 1), (1, 2), (4, 3)]);
+            assert!(runs.is_valid());
+            assert!(runs.iter().eq(&[1, 1, 1, 2, 3, 3, 3, 3]));
+            assert!(t.is_valid());
+            assert!(t.iter().eq(v.iter().cycle().take(4 * v.len())));
+            t.truncate(v.len());
+            let end = t.split_off(v.len() / 3);
+            assert!(t.is_valid() && end.is_valid());
+            assert!(t.iter().chain(end.iter()).eq(v.iter()));
+        }
+        // the smallest block size allowed
+        churn::<2>();
+        churn::<3>();
+        churn::<4>();
+    }
+    #[test]
+    fn seeded_structure() {
+        let build = |seed| {
+            let mut t: ITreap<_> = ITreap::from_iter_with_seed(0..5 * BLOCK_SIZE, seed);
+            for i in 0..3 * BLOCK_SIZE {
+                t.insert((i * 7919) % t.len(), i);
+            }
+            let mut end = t.split_off(t.len() / 3);
+            t.push_slice(&[1, 2, 3]);
+            t.append(&mut end);
+            t
+        };
+        let (t1, t2, t3) = (build(42), build(42), build(43));
+        assert!(t1.is_valid());
+        assert_eq!(t1.root.shape(), t2.root.shape());
+        assert_ne!(t1.root.shape(), t3.root.shape());
+    }
+    #[test]
+    fn between_mut() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let range = BLOCK_SIZE + 123..3 * BLOCK_SIZE + 7;
+        t.between_mut(range.clone()).for_each(|e| *e += 1);
+        assert!(t
+            .iter()
+            .enumerate()
+            .all(|(i, &e)| e == if range.contains(&i) { i + 1 } else { i }));
+        assert_eq!(t.between_mut(7..7).count(), 0);
+        assert_eq!(t.between_mut(10 * BLOCK_SIZE..11 * BLOCK_SIZE).count(), 0);
+        t.between_mut(0..5 * BLOCK_SIZE).for_each(|e| *e = 0);
+        assert!(t.iter().all(|&e| e == 0));
+    }
+    #[test]
+    fn iter_mut() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.iter_mut().for_each(|e| *e *= 2);
+        let expected: Vec<_> = (0..5 * BLOCK_SIZE).map(|e| e * 2).collect();
+        assert!(t.iter().eq(expected.iter()));
+        assert_eq!(ITreap::<u8>::new().iter_mut().count(), 0);
+    }
+    #[test]
+    fn into_iter() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let mut iter = t.into_iter();
+        assert_eq!(iter.len(), 5 * BLOCK_SIZE);
+        iter.next();
+        assert_eq!(iter.len(), 5 * BLOCK_SIZE - 1);
+        let t: ITreap<_> = iter.collect();
+        assert!(t.into_iter().eq(1..5 * BLOCK_SIZE));
+        // dropping a partially consumed iterator drops remaining elements
+        let counter = std::rc::Rc::new(());
+        let t: ITreap<_> = (0..3 * BLOCK_SIZE).map(|_| counter.clone()).collect();
+        let mut iter = t.into_iter();
+        iter.nth(BLOCK_SIZE);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 2 * BLOCK_SIZE);
+        drop(iter);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+    #[test]
+    fn clone() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.insert(17, 0);
+        let copy = t.clone();
+        assert_eq!(copy.root.shape(), t.root.shape());
+        t[3 * BLOCK_SIZE] = 0;
+        t[17] = 1;
+        assert!(copy.is_valid());
+        assert_eq!(copy[3 * BLOCK_SIZE], 3 * BLOCK_SIZE - 1);
+        assert_eq!(copy[17], 0);
+        assert!(copy.iter().ne(t.iter()));
+    }
+    #[test]
+    fn debug() {
+        let t: ITreap<_> = vec![2, 3, 7].into_iter().collect();
+        assert_eq!(format!("{:?}", t), "[2, 3, 7]");
+        assert_eq!(format!("{:#?}", t), "[\n    2,\n    3,\n    7,\n]");
+        assert_eq!(format!("{:?}", ITreap::<u8>::new()), "[]");
+    }
+    #[test]
+    fn equality() {
+        let n = 3 * BLOCK_SIZE;
+        let collected: ITreap<_> = (0..n).collect();
+        // insert in the middle to get a very different shape
+        let mut inserted = ITreap::new();
+        for e in (0..n / 2).rev() {
+            inserted.insert(0, e);
+            let len = inserted.len();
+            inserted.insert(len, n - 1 - e);
+        }
+        assert_ne!(collected.root.shape(), inserted.root.shape());
+        assert_eq!(collected, inserted);
+        let mut different = inserted.clone();
+        different[n - 1] = 0;
+        assert_ne!(collected, different);
+        different.pop();
+        assert_ne!(collected, different);
+    }
+    #[test]
+    fn ordering() {
+        let vectors = vec![
+            vec![],
+            vec![0],
+            vec![0, 0],
+            vec![0, 1],
+            vec![1],
+            (0..3 * BLOCK_SIZE).collect(),
+            (1..3 * BLOCK_SIZE).collect(),
+        ];
+        for v1 in &vectors {
+            for v2 in &vectors {
+                let t1: ITreap<_> = v1.iter().copied().collect();
+                let t2: ITreap<_> = v2.iter().copied().collect();
+                assert_eq!(t1.cmp(&t2), v1.cmp(v2));
+                assert_eq!(t1.partial_cmp(&t2), v1.partial_cmp(v2));
+            }
+        }
+    }
+    #[test]
+    fn extend() {
+        let mut t: ITreap<_> = (0..1234).collect();
+        t.extend(1234..1234 + 5 * BLOCK_SIZE);
+        t.extend(&[7, 8, 9]);
+        t.extend(std::iter::empty::<usize>());
+        assert!(t.is_valid());
+        assert!(t
+            .iter()
+            .copied()
+            .eq((0..1234 + 5 * BLOCK_SIZE).chain(7..10)));
+    }
+    #[test]
+    fn drain() {
+        let range = BLOCK_SIZE + 17..4 * BLOCK_SIZE + 3;
+        let expected = || (0..range.start).chain(range.end..5 * BLOCK_SIZE);
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        assert!(t.drain(range.clone()).eq(range.clone()));
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(expected()));
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        let mut drain = t.drain(range.clone());
+        assert_eq!(drain.nth(10), Some(range.start + 10));
+        drop(drain);
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(expected()));
+        assert_eq!(t.drain(7..7).count(), 0);
+        assert!(t.iter().copied().eq(expected()));
+    }
+    #[test]
+    fn retain() {
+        let mut t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        let mut v: Vec<_> = (0..10 * BLOCK_SIZE).collect();
+        t.retain(|e| e % 2 == 0);
+        v.retain(|e| e % 2 == 0);
+        assert!(t.is_valid());
+        assert!(t.iter().eq(v.iter()));
+        assert!(t.root.leaf_count() <= 12);
+        t.retain(|_| false);
+        assert!(t.is_valid());
+        assert!(t.is_empty());
+    }
+    #[test]
+    fn remove_fuses_leaves() {
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        let mut rng = SmallRng::seed_from_u64(269);
+        let mut t: ITreap<_> = ITreap::with_seed(269);
+        for i in 0..10 * BLOCK_SIZE {
+            t.insert(rng.gen_range(0..=i), i);
+        }
+        while t.len() > BLOCK_SIZE {
+            t.remove(rng.gen_range(0..t.len()));
+        }
+        assert!(t.is_valid());
+        assert!(t.root.leaf_count() <= 4 * t.len() / BLOCK_SIZE + 1);
+    }
+    #[test]
+    fn truncate_and_resize() {
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.truncate(6 * BLOCK_SIZE);
+        assert_eq!(t.len(), 5 * BLOCK_SIZE);
+        t.truncate(2 * BLOCK_SIZE + 3);
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..2 * BLOCK_SIZE + 3));
+        t.resize(2 * BLOCK_SIZE + 3, 0);
+        assert!(t.iter().copied().eq(0..2 * BLOCK_SIZE + 3));
+        t.resize(4 * BLOCK_SIZE, 0);
+        assert!(t.is_valid());
+        assert!(t
+            .iter()
+            .copied()
+            .eq((0..2 * BLOCK_SIZE + 3).chain(std::iter::repeat_n(0, 2 * BLOCK_SIZE - 3))));
+        t.resize(10, 0);
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..10));
+    }
+    #[test]
+    fn splice() {
+        let range = 2 * BLOCK_SIZE - 5..2 * BLOCK_SIZE + 5;
+        for replacement in [(0..50).collect::<Vec<_>>(), Vec::new()] {
+            let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+            let mut v: Vec<_> = (0..5 * BLOCK_SIZE).collect();
+            t.splice(range.clone(), replacement.clone());
+            v.splice(range.clone(), replacement);
+            assert!(t.is_valid());
+            assert!(t.iter().eq(v.iter()));
+        }
+    }
+    #[test]
+    fn reverse() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut t: ITreap<usize, 4> = ITreap::with_seed(5);
+        let mut v = Vec::new();
+        for i in 0..2_000 {
+            match rng.gen_range(0..10) {
+                0 => {
+                    t.reverse();
+                    v.reverse();
+                }
+                1 if !v.is_empty() => {
+                    let index = rng.gen_range(0..v.len());
+                    assert_eq!(t.remove(index), v.remove(index));
+                }
+                _ => {
+                    let index = rng.gen_range(0..=v.len());
+                    t.insert(index, i);
+                    v.insert(index, i);
+                }
+            }
+            let start = rng.gen_range(0..=v.len());
+            let end = rng.gen_range(start..=v.len());
+            assert!(t.between(start..end).eq(v[start..end].iter()));
+            assert_eq!(t.get(start), v.get(start));
+            assert_eq!(t.first(), v.first());
+            assert_eq!(t.last(), v.last());
+        }
+        assert!(t.is_valid());
+        assert!(t.iter().eq(v.iter()));
+        assert!(t.reversed().eq(v.iter().rev()));
+        assert!(t.clone().into_iter().eq(v.iter().copied()));
+        // sorted then reversed sequences are still searchable
+        let mut sorted: ITreap<usize, 4> = (0..1_000).rev().collect();
+        sorted.reverse();
+        assert_eq!(sorted.partition_point(|&e| e < 321), 321);
+        assert_eq!(sorted.binary_search(&500), Ok(500));
+        assert_eq!(
+            sorted.batch_search(&[3, 999, 1_000]),
+            vec![Ok(3), Ok(999), Err(1_000)]
+        );
+        assert!(sorted == (0..1_000).collect());
+        t.normalize();
+        assert!(t.chunks().flatten().eq(v.iter()));
+    }
+    #[test]
+    fn reverse_range() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut t: ITreap<usize, 4> = (0..500).collect();
+        let mut v: Vec<usize> = (0..500).collect();
+        for _ in 0..200 {
+            let start = rng.gen_range(0..=v.len());
+            let end = rng.gen_range(start..=v.len());
+            t.reverse_range(start..end);
+            v[start..end].reverse();
+            assert!(t.iter().eq(v.iter()));
+        }
+        assert!(t.is_valid());
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.reverse_range(3..3);
+        t.reverse_range(7..8);
+        assert!(t.iter().copied().eq(0..5 * BLOCK_SIZE));
+        t.reverse_range(BLOCK_SIZE..4 * BLOCK_SIZE);
+        t.reverse_range(2 * BLOCK_SIZE..5 * BLOCK_SIZE);
+        v = (0..5 * BLOCK_SIZE).collect();
+        v[BLOCK_SIZE..4 * BLOCK_SIZE].reverse();
+        v[2 * BLOCK_SIZE..5 * BLOCK_SIZE].reverse();
+        assert!(t.is_valid());
+        assert!(t.iter().eq(v.iter()));
+    }
+    #[test]
+    fn rotations() {
+        let len = 3 * BLOCK_SIZE + 7;
+        for mid in [0, 1, BLOCK_SIZE, 2 * BLOCK_SIZE + 3, len - 1, len] {
+            let mut t: ITreap<_> = (0..len).collect();
+            let mut v: Vec<_> = (0..len).collect();
+            t.rotate_left(mid);
+            v.rotate_left(mid);
+            assert!(t.is_valid());
+            assert!(t.iter().eq(v.iter()));
+            t.rotate_right(mid);
+            v.rotate_right(mid);
+            assert!(t.iter().copied().eq(0..len));
+        }
+    }
+    #[test]
+    #[should_panic(expected = "rotation point out of bounds")]
+    fn rotate_out_of_bounds() {
+        let mut t: ITreap<_> = (0..3).collect();
+        t.rotate_left(4);
+    }
+    #[test]
+    fn swap() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(11);
+        let len = 4 * BLOCK_SIZE;
+        let mut t: SumTreap<usize> = (0..len).collect();
+        let mut v: Vec<_> = (0..len).collect();
+        for _ in 0..100 {
+            let (i, j) = (rng.gen_range(0..len), rng.gen_range(0..len));
+            t.swap(i, j);
+            v.swap(i, j);
+            assert!(t.iter().eq(v.iter()));
+            t.swap(j, i);
+            v.swap(j, i);
+        }
+        assert!(t.is_valid());
+        assert!(t.iter().copied().eq(0..len));
+        assert_eq!(t.sum(), len * (len - 1) / 2);
+    }
+    #[test]
+    #[should_panic(expected = "index out of bounds: the len is 3 but the index is 3")]
+    fn swap_out_of_bounds() {
+        let mut t: ITreap<_> = (0..3).collect();
+        t.swap(0, 3);
+    }
+    #[test]
+    fn get() {
+        let len = 3 * BLOCK_SIZE;
+        let mut t: ITreap<_> = (0..len).collect();
+        for i in [0, BLOCK_SIZE / 2 - 1, BLOCK_SIZE / 2, BLOCK_SIZE, len - 1] {
+            assert_eq!(t.get(i), Some(&i));
+            assert_eq!(t.get_mut(i).copied(), Some(i));
+        }
+        assert_eq!(t.get(len), None);
+        assert_eq!(t.get_mut(len), None);
+        assert_eq!(ITreap::<usize>::new().get(0), None);
+    }
+    #[test]
+    fn extremities() {
+        let mut t: ITreap<usize> = ITreap::new();
+        assert_eq!((t.first(), t.last()), (None, None));
+        assert!(t.first_mut().is_none() && t.last_mut().is_none());
+        t.push(3);
+        assert_eq!((t.first(), t.last()), (Some(&3), Some(&3)));
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        *t.first_mut().unwrap() = 10;
+        *t.last_mut().unwrap() = 20;
+        assert_eq!(t.first(), Some(&10));
+        assert_eq!(t.last(), Some(&20));
+        assert_eq!(t[0] + t[5 * BLOCK_SIZE - 1], 30);
+    }
+    #[test]
+    fn double_ended_iter() {
+        let mut t: ITreap<usize, 4> = (0..100).collect();
+        t.reverse_range(10..60);
+        let v: Vec<_> = t.iter().copied().collect();
+        assert!(t.iter().rev().eq(v.iter().rev()));
+        assert!(t.between(5..70).rev().eq(v[5..70].iter().rev()));
+        let mut iter = t.between(20..30);
+        assert_eq!(iter.len(), 10);
+        assert_eq!(iter.next(), Some(&v[20]));
+        assert_eq!(iter.next_back(), Some(&v[29]));
+        assert_eq!(iter.len(), 8);
+        assert!(iter.by_ref().eq(&v[21..29]));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next_back(), None);
+        // both ends meeting inside a single block
+        let t: ITreap<_> = (0..10).collect();
+        let mut iter = t.iter();
+        let mut front_and_back = Vec::new();
+        while let (Some(f), Some(b)) = (iter.next(), iter.next_back()) {
+            front_and_back.push((*f, *b));
+        }
+        assert_eq!(front_and_back, vec![(0, 9), (1, 8), (2, 7), (3, 6), (4, 5)]);
+    }
+    #[test]
+    fn chunks() {
+        let mut t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        assert!(t
+            .chunks()
+            .all(|b| b.len() >= BLOCK_SIZE / 2 && b.len() <= BLOCK_SIZE));
+        for i in 0..BLOCK_SIZE {
+            t.insert((i * 7919) % t.len(), i);
+        }
+        let elements: Vec<_> = t.iter().copied().collect();
+        assert!(t.chunks().flatten().eq(&elements));
+        t.reverse();
+        t.reverse_range(BLOCK_SIZE..4 * BLOCK_SIZE + 3);
+        let elements: Vec<_> = t.iter().copied().collect();
+        assert!(t.oriented_chunks().any(|(_, backwards)| backwards));
+        assert!(t
+            .oriented_chunks()
+            .flat_map(|(b, backwards)| {
+                let mut b = b.to_vec();
+                if backwards {
+                    b.reverse();
+                }
+                b
+            })
+            .eq(elements.iter().copied()));
+        let with_blocks: Vec<_> = t.iter_with_block().map(|(b, &e)| (b, e)).collect();
+        assert!(t.chunks().flatten().eq(&elements));
+        let chunks = t.chunks().collect::<Vec<_>>();
+        assert!(with_blocks
+            .iter()
+            .all(|(block, e)| chunks[*block].contains(e)));
+        assert!(t.oriented_chunks().all(|(_, backwards)| !backwards));
+        assert!(t.is_valid());
+        let expected: Vec<_> = t.iter().map(|e| e + 1).collect();
+        t.chunks_mut().flatten().for_each(|e| *e += 1);
+        assert!(t.iter().eq(expected.iter()));
+    }
+    #[test]
+    fn range_sum() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(13);
+        let mut t: SumTreap<u64> = (0..20 * BLOCK_SIZE as u64).collect();
+        for _ in 0..BLOCK_SIZE {
+            let index = rng.gen_range(0..t.len());
+            t.insert(index, rng.gen_range(0..100));
+            t.remove(rng.gen_range(0..t.len()));
+        }
+        let mut tail = t.split_off(7 * BLOCK_SIZE + 3);
+        t.append(&mut tail);
+        let prefix_sums: Vec<u64> = std::iter::once(0)
+            .chain(t.iter().scan(0, |sum, e| {
+                *sum += e;
+                Some(*sum)
+            }))
+            .collect();
+        for _ in 0..200 {
+            let start = rng.gen_range(0..=t.len());
+            let end = rng.gen_range(start..=t.len());
+            assert_eq!(
+                t.range_sum(start..end),
+                prefix_sums[end] - prefix_sums[start]
+            );
+        }
+        assert_eq!(t.range_sum(0..t.len()), t.sum());
+    }
+    #[test]
+    fn binary_search() {
+        let len = 5 * BLOCK_SIZE;
+        let t: ITreap<_> = (0..len).map(|e| 2 * e + 1).collect();
+        assert_eq!(t.binary_search(&1), Ok(0));
+        assert_eq!(t.binary_search(&(2 * len - 1)), Ok(len - 1));
+        assert_eq!(t.binary_search(&(2 * BLOCK_SIZE + 1)), Ok(BLOCK_SIZE));
+        assert_eq!(t.binary_search(&0), Err(0));
+        assert_eq!(t.binary_search(&(2 * BLOCK_SIZE)), Err(BLOCK_SIZE));
+        assert_eq!(t.binary_search(&(2 * len)), Err(len));
+        assert_eq!(t.partition_point(|&e| e < 2 * BLOCK_SIZE), BLOCK_SIZE);
+        assert_eq!(t.partition_point(|_| true), len);
+        assert_eq!(t.partition_point(|_| false), 0);
+    }
+    #[test]
+    fn insert_sorted() {
+        use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+        let mut values: Vec<_> = (0..3 * BLOCK_SIZE).map(|e| e / 2).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(17));
+        let mut t = ITreap::new();
+        for &value in &values {
+            let index = t.insert_sorted(value);
+            assert_eq!(t[index], value);
+        }
+        values.sort_unstable();
+        assert!(t.is_valid());
+        assert!(t.iter().eq(values.iter()));
+    }
+    #[test]
+    fn select_by_weight() {
+        use super::{Weight, WeightedTreap};
+        struct Weighted(u64);
+        impl Weight for Weighted {
+            fn weight(&self) -> u64 {
+                self.0
+            }
+        }
+        let len = 3 * BLOCK_SIZE;
+        let units: WeightedTreap<_> = (0..len).map(|_| Weighted(1)).collect();
+        for i in [0, 1, BLOCK_SIZE, len - 1] {
+            assert_eq!(units.select_by_weight(i as u64).map(|(j, _)| j), Some(i));
+        }
+        assert!(units.select_by_weight(len as u64).is_none());
+        let mut t: WeightedTreap<_> = (0..len as u64).map(|e| Weighted(e % 7)).collect();
+        for i in 0..BLOCK_SIZE as u64 {
+            t.insert((i as usize * 31) % t.len(), Weighted(i % 5));
+            t.remove((i as usize * 17) % t.len());
+        }
+        let weights: Vec<u64> = t.iter().map(|e| e.0).collect();
+        assert_eq!(t.total_weight(), weights.iter().sum::<u64>());
+        for w in (0..t.total_weight() + 10).step_by(97) {
+            let mut total = 0;
+            let expected = weights.iter().position(|weight| {
+                total += weight;
+                total > w
+            });
+            assert_eq!(t.select_by_weight(w).map(|(i, _)| i), expected);
+        }
+    }
+    #[test]
+    fn cursor() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(19);
+        let mut t: ITreap<usize, 8> = (0..100).collect();
+        let mut v: Vec<usize> = (0..100).collect();
+        let mut position = 37;
+        let mut cursor = t.cursor_at(position);
+        for i in 0..2_000 {
+            match rng.gen_range(0..4) {
+                0 => {
+                    assert_eq!(cursor.move_next(), position < v.len());
+                    position = (position + 1).min(v.len());
+                }
+                1 => {
+                    assert_eq!(cursor.move_prev(), position > 0);
+                    position = position.saturating_sub(1);
+                }
+                2 => {
+                    cursor.insert(1_000 + i);
+                    v.insert(position, 1_000 + i);
+                    position += 1;
+                }
+                _ => {
+                    let expected = (position < v.len()).then(|| v.remove(position));
+                    assert_eq!(cursor.remove(), expected);
+                }
+            }
+            assert_eq!(cursor.index(), position);
+            assert_eq!(cursor.current(), v.get(position));
+        }
+        drop(cursor);
+        assert!(t.is_valid());
+        assert!(t.iter().eq(v.iter()));
+        let mut cursor = t.cursor_at(v.len());
+        assert_eq!(cursor.current(), None);
+        assert!(!cursor.move_next());
+        cursor.insert(7);
+        drop(cursor);
+        assert_eq!(t.last(), Some(&7));
+    }
+    #[test]
+    fn concat() {
+        let a: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        let b: ITreap<_> = (0..BLOCK_SIZE / 3).collect();
+        let expected: Vec<_> = a.iter().chain(b.iter()).copied().collect();
+        let mut c = a.clone() + b.clone();
+        assert!(c.is_valid());
+        assert!(c.iter().eq(expected.iter()));
+        c += b;
+        assert_eq!(c.len(), expected.len() + BLOCK_SIZE / 3);
+        let parts = (0..20).map(|i| (i * 100..i * 100 + i).collect::<ITreap<_>>());
+        let whole = ITreap::concat(parts);
+        assert!(whole.is_valid());
+        assert!(whole
+            .iter()
+            .copied()
+            .eq((0..20).flat_map(|i| i * 100..i * 100 + i)));
+        assert!(ITreap::<u8>::concat(Vec::new()).is_empty());
+    }
+    #[test]
+    fn diagnostics() {
+        let n = 1_000 * BLOCK_SIZE;
+        let mut t: ITreap<u32> = (0..n as u32).collect();
+        let bound = ((n / BLOCK_SIZE) as f64).log2().ceil() as usize;
+        assert!(t.depth() <= 2 * bound + 2);
+        assert_eq!(t.leaf_count(), t.chunks().count());
+        let payload = n * std::mem::size_of::<u32>();
+        assert!(t.memory_usage() >= payload);
+        assert!(t.memory_usage() <= 2 * payload);
+        let empty = ITreap::<u32>::new();
+        assert_eq!((empty.depth(), empty.leaf_count()), (0, 1));
+    }
+    #[test]
+    fn drop_degenerate_tree() {
+        let mut root = Node::Leaf(vec![0]);
+        for i in 1..300_000 {
+            root = Node::inner(i as u64, [Box::new(Node::Leaf(vec![i])), Box::new(root)]);
+        }
+        let t: ITreap<usize, 2> = ITreap::from_root(root);
+        assert_eq!(t.len(), 300_000);
+        drop(t);
+    }
+    #[test]
+    fn vec_conversions() {
+        for len in [0, 1, 10 * BLOCK_SIZE + 3] {
+            let v: Vec<_> = (0..len).collect();
+            let t = ITreap::<_>::from(v.clone());
+            assert!(t.is_valid());
+            assert_eq!(t.to_vec(), v);
+            assert_eq!(t.into_vec(), v);
+        }
+    }
+    #[test]
+    fn persistent() {
+        use super::PersistentITreap;
+        use std::rc::Rc;
+        let mut t: PersistentITreap<usize, 8> = (0..8_000).collect();
+        let snapshot = t.clone();
+        assert_eq!(Rc::strong_count(&t.root), 2);
+        t.insert(1_234, 0);
+        let shared: std::collections::HashSet<_> = snapshot.node_addresses().into_iter().collect();
+        let copied = t
+            .node_addresses()
+            .into_iter()
+            .filter(|address| !shared.contains(address))
+            .count();
+        assert!(copied < 100);
+        let mut v: Vec<usize> = (0..8_000).collect();
+        v.insert(1_234, 0);
+        for i in 0..1_000 {
+            let index = (i * 7_919) % v.len();
+            assert_eq!(t.remove(index), v.remove(index));
+            t.insert(index / 2, i);
+            v.insert(index / 2, i);
+        }
+        assert!(t.iter().eq(v.iter()));
+        assert_eq!(t.get(v.len()), None);
+        assert!(snapshot.iter().copied().eq(0..8_000));
+    }
+    #[test]
+    fn with_capacity() {
+        let mut t: ITreap<_> = ITreap::with_capacity(10 * BLOCK_SIZE);
+        let capacity = t.capacity();
+        assert!(capacity >= 10 * BLOCK_SIZE);
+        let addresses: Vec<*const usize> = (0..10 * BLOCK_SIZE)
+            .map(|i| {
+                t.push(i);
+                t.last().unwrap() as *const usize
+            })
+            .collect();
+        // no block reallocated: all elements stayed where they were pushed
+        assert!(t.iter().map(|e| e as *const usize).eq(addresses));
+        assert_eq!(t.capacity(), capacity);
+        assert_eq!(t.leaf_count(), 10);
+        assert!(t.is_valid());
+        let mut t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        t.reserve(3 * BLOCK_SIZE);
+        let capacity = t.capacity();
+        assert!(capacity >= 8 * BLOCK_SIZE);
+        (0..3 * BLOCK_SIZE).for_each(|i| t.push(i));
+        assert_eq!(t.capacity(), capacity);
+        t.shrink_to_fit();
+        assert!(t.capacity() < capacity);
+    }
+    #[test]
+    fn extend_from_slice() {
+        let mut t: ITreap<_> = (0..BLOCK_SIZE + 7).collect();
+        let mut v: Vec<_> = (0..BLOCK_SIZE + 7).collect();
+        for len in [0, 1, BLOCK_SIZE / 2, 5 * BLOCK_SIZE + 3] {
+            let items: Vec<_> = (0..len).map(|e| e * 3).collect();
+            t.extend_from_slice(&items);
+            v.extend_from_slice(&items);
+            assert!(t.is_valid());
+            assert!(t.iter().eq(v.iter()));
+        }
+    }
+    #[test]
+    fn linear_searches() {
+        let t: ITreap<_> = (0..5 * BLOCK_SIZE).collect();
+        for i in [0, BLOCK_SIZE - 1, BLOCK_SIZE, 5 * BLOCK_SIZE - 1] {
+            assert!(t.contains(&i));
+            assert_eq!(t.position(|&e| e == i), Some(i));
+            assert_eq!(t.find(|&&e| e >= i), Some(&i));
+        }
+        assert!(!t.contains(&(5 * BLOCK_SIZE)));
+        assert_eq!(t.position(|&e| e > 5 * BLOCK_SIZE), None);
+        let empty = ITreap::<usize>::new();
+        assert!(!empty.contains(&0));
+        assert_eq!(empty.position(|_| true), None);
+        assert_eq!(empty.find(|_| true), None);
+    }
+    #[test]
+    fn get_disjoint_mut() {
+        let n = 10 * BLOCK_SIZE;
+        let mut t: ITreap<_> = (0..n).collect();
+        t.reverse_range(BLOCK_SIZE..5 * BLOCK_SIZE);
+        let mut v: Vec<_> = t.iter().copied().collect();
+        let indices = [n - 1, 0, 3 * BLOCK_SIZE, BLOCK_SIZE / 2, 7 * BLOCK_SIZE + 1];
+        let borrows = t.get_disjoint_mut(indices).unwrap();
+        for (slot, &i) in indices.iter().enumerate() {
+            assert_eq!(*borrows[slot], v[i]);
+            *borrows[slot] += n;
+            v[i] += n;
+        }
+        assert!(t.iter().eq(&v));
+        assert!(t.get_disjoint_mut([3, 5, 3]).is_none());
+        assert!(t.get_disjoint_mut([0, n]).is_none());
+        assert!(t.get_disjoint_mut([]).is_some());
+    }
+    #[test]
+    fn for_loops() {
+        let mut t: ITreap<_> = (0..3 * BLOCK_SIZE).collect();
+        t.reverse_range(BLOCK_SIZE / 2..2 * BLOCK_SIZE);
+        let expected: Vec<_> = t.iter().map(|e| 2 * e).collect();
+        for e in &mut t {
+            *e *= 2;
+        }
+        let mut seen = Vec::new();
+        for e in &t {
+            seen.push(*e);
+        }
+        assert_eq!(seen, expected);
+        for e in &mut ITreap::<usize>::new() {
+            *e += 1;
+        }
+    }
+    #[test]
+    fn hash() {
+        let n = 3 * BLOCK_SIZE;
+        let collected: ITreap<_> = (0..n).collect();
+        let mut inserted = ITreap::new();
+        (0..n).rev().for_each(|e| inserted.insert(0, e));
+        let mut reversed: ITreap<_> = (0..n).rev().collect();
+        reversed.reverse();
+        assert_ne!(collected.leaf_count(), inserted.leaf_count());
+        let set: std::collections::HashSet<_> =
+            vec![collected, inserted, reversed].into_iter().collect();
+        assert_eq!(set.len(), 1);
+        let other: ITreap<_> = (1..=n).collect();
+        assert!(!set.contains(&other));
+    }
+    #[test]
+    fn rebalance() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut t: ITreap<usize> = ITreap::with_seed(0);
+        for e in 0..30 * BLOCK_SIZE {
+            let index = rng.gen_range(0..=t.len());
+            t.insert(index, e);
+            if e % 3 == 0 {
+                let index = rng.gen_range(0..t.len());
+                t.remove(index);
+            }
+        }
+        let elements: Vec<_> = t.iter().copied().collect();
+        let depth = t.depth();
+        t.rebalance();
+        assert!(t.is_valid());
+        assert!(t.iter().eq(&elements));
+        let collected: ITreap<_> = elements.iter().copied().collect();
+        assert!(t
+            .oriented_chunks()
+            .map(|(b, _)| b.len())
+            .eq(collected.oriented_chunks().map(|(b, _)| b.len())));
+        let min_leaves = elements.len().div_ceil(BLOCK_SIZE / 2);
+        assert_eq!(t.leaf_count(), min_leaves);
+        assert_eq!(
+            t.depth(),
+            min_leaves.next_power_of_two().trailing_zeros() as usize
+        );
+        assert!(t.depth() < depth);
+        // nearly empty leaves are gathered
+        let mut sparse = fragmented(4, 10);
+        sparse.rebalance();
+        assert!(sparse.is_valid());
+        assert!(sparse.iter().copied().eq(0..160));
+        assert_eq!(sparse.leaf_count(), 1);
+        t.reserve(BLOCK_SIZE);
+        t.shrink_to_fit();
+        assert!(t.capacity() >= t.len() && t.capacity() < t.len() + BLOCK_SIZE);
+    }
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_iter() {
+        use rayon::prelude::*;
+        let mut t: ITreap<u64> = (0..100 * BLOCK_SIZE as u64).collect();
+        t.reverse_range(1_000..50_000);
+        assert_eq!(
+            t.par_iter().map(|e| e * 3).sum::<u64>(),
+            t.iter().map(|e| e * 3).sum::<u64>()
+        );
+        let collected: Vec<_> = t.par_iter().copied().collect();
+        assert!(t.iter().eq(collected.iter()));
+        assert_eq!(t.par_iter().len(), t.len());
+        t.reverse();
+        let mut indexed = Vec::new();
+        t.par_iter().copied().collect_into_vec(&mut indexed);
+        assert!(t.iter().eq(indexed.iter()));
+        assert!(t.par_iter().enumerate().all(|(i, e)| t[i] == *e));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let t: ITreap<_> = (0..10 * BLOCK_SIZE).collect();
+        let json = serde_json::to_string(&t).unwrap();
+        let back: ITreap<usize> = serde_json::from_str(&json).unwrap();
+        assert!(back.is_valid());
+        assert_eq!(back, t);
+        let empty = serde_json::to_string(&ITreap::<u8>::new()).unwrap();
+        assert_eq!(empty, "[]");
+        assert!(serde_json::from_str::<ITreap<u8>>(&empty)
+            .unwrap()
+            .is_empty());
+        let parsed: ITreap<u8> = serde_json::from_str("[2, 3, 7]").unwrap();
+        assert!(parsed.iter().eq(&[2, 3, 7]));
+        // a lying size hint must not trigger a huge allocation
+        struct Liar(std::ops::Range<u8>);
+        impl Iterator for Liar {
+            type Item = u8;
+            fn next(&mut self) -> Option<u8> {
+                self.0.next()
+            }
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (usize::MAX, Some(usize::MAX))
+            }
+        }
+        use serde::{de::value::SeqDeserializer, Deserialize};
+        let input = SeqDeserializer::<_, serde::de::value::Error>::new(Liar(0..10));
+        let t = ITreap::<u8>::deserialize(input).unwrap();
+        assert!(t.iter().copied().eq(0..10));
+    }
+    #[cfg(feature = "dot")]
+    #[test]
+    fn dot() {
+        let t: ITreap<_> = (0..4 * BLOCK_SIZE).collect();
+        let dot = t.to_dot();
+        // 8 leaves and 7 inner nodes
+        assert_eq!(dot.matches("label=").count(), 15);
+        assert_eq!(dot.matches("->").count(), 14);
+    }
 }
@@ -0,0 +1,57 @@
+/// A summary of a sequence of elements, cached in all inner nodes of the tree.
+///
+/// Summaries of consecutive sub-sequences are combined associatively,
+/// `identity` being the summary of an empty sequence.
+/// The default summary `()` costs nothing.
+pub trait Monoid<C>: Clone {
+    /// The summary of an empty sequence.
+    fn identity() -> Self;
+    /// The summary of a single element.
+    fn lift(element: &C) -> Self;
+    /// The summary of `self`'s sequence followed by `other`'s sequence.
+    fn combine(&self, other: &Self) -> Self;
+}
+
+impl<C> Monoid<C> for () {
+    fn identity() -> Self {}
+    fn lift(_element: &C) -> Self {}
+    fn combine(&self, _other: &Self) -> Self {}
+}
+
+/// Sums of elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sum<C>(pub C);
+
+impl<C: core::ops::Add<Output = C> + Default + Copy> Monoid<C> for Sum<C> {
+    fn identity() -> Self {
+        Sum(C::default())
+    }
+    fn lift(element: &C) -> Self {
+        Sum(*element)
+    }
+    fn combine(&self, other: &Self) -> Self {
+        Sum(self.0 + other.0)
+    }
+}
+
+/// Elements carrying a weight, see `TotalWeight`.
+pub trait Weight {
+    /// The weight of the element.
+    fn weight(&self) -> u64;
+}
+
+/// Sums of weights of elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TotalWeight(pub u64);
+
+impl<C: Weight> Monoid<C> for TotalWeight {
+    fn identity() -> Self {
+        TotalWeight(0)
+    }
+    fn lift(element: &C) -> Self {
+        TotalWeight(element.weight())
+    }
+    fn combine(&self, other: &Self) -> Self {
+        TotalWeight(self.0 + other.0)
+    }
+}
@@ -1,4 +1,7 @@
-use rand::random;
+use super::Monoid;
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use core::ops::Range;
+use rand::Rng;
 use replace_with::replace_with_or_abort;
 pub(super) const BLOCK_SIZE: usize = 1000;
 pub(super) const LEFT: usize = 0;
@@ -6,21 +9,151 @@ pub(super) const RIGHT: usize = 1;
 
 pub(super) type Priority = u64;
 
-pub(super) enum Node<C> {
+#[derive(Clone)]
+pub(super) enum Node<C, const B: usize = BLOCK_SIZE, M = ()> {
     Leaf(Vec<C>),
-    Inner(Priority, usize, [Box<Node<C>>; 2]),
+    /// Inner nodes record their priority, their size, the summary of all elements below
+    /// and whether their content is lazily reversed.
+    Inner(Priority, usize, M, bool, [Box<Node<C, B, M>>; 2]),
 }
 
-impl<C> Node<C> {
-    pub(super) fn is_valid(&self, father: Option<&Self>) -> bool {
+impl<C, const B: usize, M> Node<C, B, M> {
+    /// Blocks need to split into two non-empty halves.
+    /// Referencing this constant rejects smaller block sizes at compile time.
+    pub(super) const VALID_BLOCK_SIZE: () =
+        assert!(B >= 2, "blocks must hold at least two elements");
+    /// Reverses the content of the node.
+    /// Leaves are reversed right away while inner nodes just toggle their flag.
+    pub fn toggle_reversal(&mut self) {
         match self {
-            Node::Inner(_, _, children) => {
-                father
-                    .map(|f| self.priority() <= f.priority())
-                    .unwrap_or(true)
-                    && children.iter().all(|child| child.is_valid(Some(self)))
+            Node::Leaf(block) => block.reverse(),
+            Node::Inner(_, _, _, reversed, _) => *reversed = !*reversed,
+        }
+    }
+    /// Drops the node and everything below it using an explicit stack of nodes
+    /// since recursively dropping a degenerate tree could overflow the call stack.
+    pub fn dismantle(self) {
+        let mut remaining_nodes = vec![self];
+        while let Some(node) = remaining_nodes.pop() {
+            if let Node::Inner(_, _, _, _, [left, right]) = node {
+                remaining_nodes.push(*left);
+                remaining_nodes.push(*right);
+            }
+        }
+    }
+    /// Applies a pending reversal of the node to its children.
+    /// Cost is O(B).
+    pub fn push_down(&mut self) {
+        if let Node::Inner(_, _, _, reversed, children) = self {
+            if *reversed {
+                *reversed = false;
+                children.swap(LEFT, RIGHT);
+                children
+                    .iter_mut()
+                    .for_each(|child| child.toggle_reversal());
+            }
+        }
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> Node<C, B, M> {
+    /// Builds an inner node, computing its size and summary from its children.
+    pub fn inner(priority: Priority, children: [Box<Self>; 2]) -> Self {
+        let size = children[LEFT].len() + children[RIGHT].len();
+        let summary = children[LEFT].summary().combine(&children[RIGHT].summary());
+        Node::Inner(priority, size, summary, false, children)
+    }
+    /// Recomputes size and summary after a change in the children.
+    pub fn fix(&mut self) {
+        if let Node::Inner(_, size, summary, _, children) = self {
+            *size = children[LEFT].len() + children[RIGHT].len();
+            *summary = children[LEFT].summary().combine(&children[RIGHT].summary());
+        }
+    }
+    /// Returns the summary of all elements below the node.
+    /// Cost is O(1) for inner nodes and O(B) for leaves.
+    pub fn summary(&self) -> M {
+        match self {
+            Node::Leaf(block) => fold_block(block),
+            Node::Inner(_, _, summary, _, _) => summary.clone(),
+        }
+    }
+    /// Returns the summary of elements in given range of indices.
+    /// Only nodes crossing the range boundaries are visited.
+    /// Cost is O(log(n/B)+B).
+    pub fn range_summary(&self, range: Range<usize>) -> M {
+        if range.start >= range.end {
+            M::identity()
+        } else if range.start == 0 && range.end >= self.len() {
+            self.summary()
+        } else {
+            match self {
+                Node::Leaf(block) => fold_block(&block[range]),
+                // pending reversals only happen without summaries
+                Node::Inner(_, _, _, _, children) => {
+                    let left_size = children[LEFT].len();
+                    let left = children[LEFT]
+                        .range_summary(range.start.min(left_size)..range.end.min(left_size));
+                    let right = children[RIGHT].range_summary(
+                        range.start.saturating_sub(left_size)..range.end.saturating_sub(left_size),
+                    );
+                    left.combine(&right)
+                }
+            }
+        }
+    }
+    /// Checks all invariants below the node, reporting the first violation.
+    /// `path` is the sequence of directions taken from the root.
+    pub(super) fn validate(&self, father: Option<&Self>, path: &mut String) -> Result<(), String> {
+        let location = |path: &String| {
+            if path.is_empty() {
+                "root".to_owned()
+            } else {
+                format!("depth {} (path {})", path.len(), path)
+            }
+        };
+        match self {
+            Node::Inner(priority, size, _, _, children) => {
+                if let Some(father_priority) = father.map(|f| f.priority()) {
+                    if *priority > father_priority {
+                        return Err(format!(
+                            "heap order violated at {}: priority {} above father's {}",
+                            location(path),
+                            priority,
+                            father_priority
+                        ));
+                    }
+                }
+                let children_size = children[LEFT].len() + children[RIGHT].len();
+                if *size != children_size {
+                    return Err(format!(
+                        "size mismatch at {}: recorded {} but children hold {}",
+                        location(path),
+                        size,
+                        children_size
+                    ));
+                }
+                for (child, direction) in children.iter().zip(&['L', 'R']) {
+                    path.push(*direction);
+                    child.validate(Some(self), path)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            Node::Leaf(block) => {
+                if block.len() > B {
+                    Err(format!(
+                        "oversized leaf at {}: {} elements for blocks of {}",
+                        location(path),
+                        block.len(),
+                        B
+                    ))
+                } else if block.is_empty() && father.is_some() {
+                    Err(format!("empty non-root leaf at {}", location(path)))
+                } else {
+                    Ok(())
+                }
             }
-            _ => self.len() > 0 || father.is_none(),
         }
     }
     pub fn rotate(&mut self, direction: usize) {
@@ -32,17 +165,15 @@ impl<C> Node<C> {
             let (self_priority, [n1, n2]) = owned_self.extract_content(direction);
             let (n2_priority, [n3, n4]) = n2.extract_content(direction);
             assert!(self_priority <= n2_priority);
-            let new_self_size = n1.len() + n3.len();
-            let new_self = Box::new(Node::Inner(self_priority, new_self_size, [n1, n3]));
-            let new_n2_size = new_self_size + n4.len();
-            let new_n2 = Node::Inner(n2_priority, new_n2_size, [new_self, n4]);
-            new_n2
+            let new_self = Box::new(Node::inner(self_priority, oriented([n1, n3], direction)));
+            Node::inner(n2_priority, oriented([new_self, n4], direction))
         })
     }
-    pub fn extract_content(self, direction: usize) -> (u64, [Box<Node<C>>; 2]) {
+    pub fn extract_content(mut self, direction: usize) -> (u64, [Box<Self>; 2]) {
+        self.push_down();
         let (priority, mut children) = match self {
             Node::Leaf(_) => panic!("extracting children from a leaf"),
-            Node::Inner(priority, _, children) => (priority, children),
+            Node::Inner(priority, _, _, _, children) => (priority, children),
         };
         if direction == RIGHT {
             children.swap(0, 1)
@@ -51,34 +182,168 @@ impl<C> Node<C> {
     }
     pub fn priority(&self) -> Priority {
         match self {
-            Node::Leaf(_) => std::u64::MAX, // it's a lie but a good one : leaves never go up
-            Node::Inner(priority, _, _) => *priority,
+            Node::Leaf(_) => Priority::MIN, // it's a lie but a good one : leaves never go up
+            Node::Inner(priority, _, _, _, _) => *priority,
         }
     }
-    pub fn insert(&mut self, index: usize, element: C) {
-        if self.is_leaf() && self.len() == BLOCK_SIZE {
-            self.divide()
+    /// Applies all pending reversals below the node.
+    /// Cost is O(n).
+    pub fn normalize(&mut self) {
+        self.push_down();
+        if let Node::Inner(_, _, _, _, children) = self {
+            children.iter_mut().for_each(|child| child.normalize());
         }
+    }
+    pub fn insert<R: Rng + ?Sized>(&mut self, index: usize, element: C, rng: &mut R) {
+        if self.is_leaf() && self.len() == B {
+            self.divide(rng)
+        }
+        self.push_down();
         match self {
             Node::Leaf(block) => {
                 block.insert(index, element);
             }
-            Node::Inner(_, size, children) => {
-                *size += 1;
+            Node::Inner(_, _, _, _, children) => {
                 let left_size = children[LEFT].len();
+                // unlike `get`, index == left_size is valid on the left side:
+                // it appends at the end of the left child
                 let (direction, remaining_index) = if left_size >= index {
                     (LEFT, index)
                 } else {
                     (RIGHT, index - left_size)
                 };
-                children[direction].insert(remaining_index, element);
-                if children[direction].priority() > self.priority() {
+                children[direction].insert(remaining_index, element, rng);
+                let rotate = children[direction].priority() > self.priority();
+                self.fix();
+                if rotate {
                     self.rotate(1 - direction)
                 }
             }
         }
     }
-    pub fn divide(&mut self) {
+    /// Removes and returns the element at given index.
+    /// Inner nodes left with an empty child are replaced by their other child.
+    /// A leaf child falling under half a block is fused into the neighbouring
+    /// leaf of its sibling when they fit together, getting rid of the child.
+    pub fn remove(&mut self, index: usize) -> C {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => block.remove(index),
+            Node::Inner(_, _, _, _, children) => {
+                let left_size = children[LEFT].len();
+                let (direction, remaining_index) = if index < left_size {
+                    (LEFT, index)
+                } else {
+                    (RIGHT, index - left_size)
+                };
+                let removed = children[direction].remove(remaining_index);
+                let small_size = match children[direction].as_ref() {
+                    Node::Leaf(block) if block.len() < B / 2 => Some(block.len()),
+                    _ => None,
+                };
+                let fits = |size| size + children[1 - direction].extreme_leaf_len(direction) <= B;
+                if small_size.map(fits).unwrap_or(false) {
+                    if let Node::Leaf(block) = children[direction].as_mut() {
+                        let elements = core::mem::take(block);
+                        children[1 - direction].absorb(direction, elements);
+                    }
+                }
+                if children[direction].len() == 0 {
+                    replace_with_or_abort(self, |owned_self| {
+                        let (_, [_, other]) = owned_self.extract_content(direction);
+                        *other
+                    })
+                } else {
+                    self.fix()
+                }
+                removed
+            }
+        }
+    }
+    /// Returns the size of the leaf at the extremity of given side.
+    pub fn extreme_leaf_len(&self, side: usize) -> usize {
+        match self {
+            Node::Leaf(block) => block.len(),
+            Node::Inner(_, _, _, reversed, children) => {
+                let side = if *reversed { 1 - side } else { side };
+                children[side].extreme_leaf_len(side)
+            }
+        }
+    }
+    /// Adds given elements on given side of the leaf at this extremity.
+    fn absorb(&mut self, side: usize, elements: Vec<C>) {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => {
+                if side == LEFT {
+                    block.splice(0..0, elements);
+                } else {
+                    block.extend(elements)
+                }
+            }
+            Node::Inner(_, _, _, _, children) => {
+                children[side].absorb(side, elements);
+                self.fix()
+            }
+        }
+    }
+    /// Replaces the element at given index, returning the previous one.
+    pub fn replace(&mut self, index: usize, element: C) -> C {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => core::mem::replace(&mut block[index], element),
+            Node::Inner(_, _, _, _, children) => {
+                let left_size = children[LEFT].len();
+                let replaced = if index < left_size {
+                    children[LEFT].replace(index, element)
+                } else {
+                    children[RIGHT].replace(index - left_size, element)
+                };
+                self.fix();
+                replaced
+            }
+        }
+    }
+    /// Applies given function to the element at given index,
+    /// fixing summaries on the way back up.
+    fn update<F: FnOnce(&mut C)>(&mut self, index: usize, f: F) {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => f(&mut block[index]),
+            Node::Inner(_, _, _, _, children) => {
+                let left_size = children[LEFT].len();
+                if index < left_size {
+                    children[LEFT].update(index, f)
+                } else {
+                    children[RIGHT].update(index - left_size, f)
+                }
+                self.fix()
+            }
+        }
+    }
+    /// Swaps elements at indices `i` and `j`, with `i < j`.
+    /// We go down together until the paths to both elements diverge.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => block.swap(i, j),
+            Node::Inner(_, _, _, _, children) => {
+                let left_size = children[LEFT].len();
+                if j < left_size {
+                    children[LEFT].swap(i, j)
+                } else if i >= left_size {
+                    children[RIGHT].swap(i - left_size, j - left_size)
+                } else {
+                    let [left, right] = children;
+                    left.update(i, |a| {
+                        right.update(j - left_size, |b| core::mem::swap(a, b))
+                    })
+                }
+                self.fix()
+            }
+        }
+    }
+    pub fn divide<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         replace_with_or_abort(self, |owned_self| {
             let mut block: Vec<C> = match owned_self {
                 Node::Leaf(inner_block) => inner_block,
@@ -86,9 +351,8 @@ impl<C> Node<C> {
             };
             let size = block.len();
             let right_block = block.split_off(size / 2);
-            Node::Inner(
-                random(),
-                size,
+            Node::inner(
+                rng.gen(),
                 [
                     Box::new(Node::Leaf(block)),
                     Box::new(Node::Leaf(right_block)),
@@ -96,24 +360,312 @@ impl<C> Node<C> {
             )
         });
     }
+    /// Splits the node in two : all elements before `index` and all elements after.
+    /// Both resulting nodes respect the heap property and contain no empty leaf
+    /// (except if they are totally empty).
+    pub fn split(mut self, index: usize) -> (Self, Self) {
+        self.push_down();
+        match self {
+            Node::Leaf(mut block) => {
+                let right_block = block.split_off(index);
+                (Node::Leaf(block), Node::Leaf(right_block))
+            }
+            Node::Inner(priority, _, _, _, [left, right]) => {
+                let left_size = left.len();
+                if index <= left_size {
+                    let (left_left, left_right) = left.split(index);
+                    (left_left, Node::with_children(priority, left_right, *right))
+                } else {
+                    let (right_left, right_right) = right.split(index - left_size);
+                    (
+                        Node::with_children(priority, *left, right_left),
+                        right_right,
+                    )
+                }
+            }
+        }
+    }
+    /// Builds a node from two children, skipping empty ones.
+    fn with_children(priority: Priority, left: Self, right: Self) -> Self {
+        if left.len() == 0 {
+            right
+        } else if right.len() == 0 {
+            left
+        } else {
+            Node::inner(priority, [Box::new(left), Box::new(right)])
+        }
+    }
+    /// Concatenates two nodes.
+    /// Adjacent leaves are fused when their combined size fits in a block.
+    pub fn join<R: Rng + ?Sized>(left: Self, right: Self, rng: &mut R) -> Self {
+        if left.len() == 0 {
+            return right;
+        }
+        if right.len() == 0 {
+            return left;
+        }
+        match (left, right) {
+            (Node::Leaf(mut left_block), Node::Leaf(right_block)) => {
+                if left_block.len() + right_block.len() <= B {
+                    left_block.extend(right_block);
+                    Node::Leaf(left_block)
+                } else {
+                    // we create a new inner node, it will go up with rotations
+                    // if its priority is too high
+                    Node::inner(
+                        rng.gen(),
+                        [
+                            Box::new(Node::Leaf(left_block)),
+                            Box::new(Node::Leaf(right_block)),
+                        ],
+                    )
+                }
+            }
+            (left, right) => {
+                let direction = if left.priority() >= right.priority() {
+                    LEFT
+                } else {
+                    RIGHT
+                };
+                // we keep the root of the highest priority node
+                // and join its inner side with the other node
+                let (root, other) = if direction == LEFT {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                let (priority, [outer, inner]) = root.extract_content(direction);
+                let joined = if direction == LEFT {
+                    Node::join(*inner, other, rng)
+                } else {
+                    Node::join(other, *inner, rng)
+                };
+                let mut node =
+                    Node::inner(priority, oriented([outer, Box::new(joined)], direction));
+                if let Node::Inner(_, _, _, _, children) = &node {
+                    if children[1 - direction].priority() > priority {
+                        node.rotate(direction)
+                    }
+                }
+                node
+            }
+        }
+    }
+    /// Keeps only elements satisfying given predicate.
+    /// Subtrees are joined back together on the way up, which fuses small
+    /// adjacent leaves and gets rid of empty ones.
+    pub fn retain<F: FnMut(&C) -> bool, R: Rng + ?Sized>(mut self, f: &mut F, rng: &mut R) -> Self {
+        self.push_down();
+        match self {
+            Node::Leaf(mut block) => {
+                block.retain(|e| f(e));
+                Node::Leaf(block)
+            }
+            Node::Inner(_, _, _, _, [left, right]) => {
+                let left = left.retain(f, rng);
+                let right = right.retain(f, rng);
+                Node::join(left, right, rng)
+            }
+        }
+    }
+    /// Fuses sibling leaves whose combined size fits in a block.
+    pub fn coalesce(&mut self) {
+        self.push_down();
+        if let Node::Inner(_, _, _, _, children) = self {
+            children.iter_mut().for_each(|child| child.coalesce());
+            if children.iter().all(|child| child.is_leaf()) && self.len() <= B {
+                replace_with_or_abort(self, |owned_self| {
+                    let (_, [left, right]) = owned_self.extract_content(LEFT);
+                    match (*left, *right) {
+                        (Node::Leaf(mut left_block), Node::Leaf(right_block)) => {
+                            left_block.extend(right_block);
+                            Node::Leaf(left_block)
+                        }
+                        _ => unreachable!(),
+                    }
+                })
+            }
+        }
+    }
     pub fn is_leaf(&self) -> bool {
+        matches!(self, Node::Leaf(_))
+    }
+    /// Returns the maximal number of edges from the node down to a leaf.
+    pub fn depth(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Inner(_, _, _, _, children) => {
+                1 + children[LEFT].depth().max(children[RIGHT].depth())
+            }
+        }
+    }
+    /// Lists (priority, size) of all nodes below this one, in prefix order.
+    #[cfg(test)]
+    pub fn shape(&self) -> Vec<(Priority, usize)> {
+        let mut shape = vec![(self.priority(), self.len())];
+        if let Node::Inner(_, _, _, _, children) = self {
+            children
+                .iter()
+                .for_each(|child| shape.extend(child.shape()));
+        }
+        shape
+    }
+    /// Returns the number of leaves below the node.
+    pub fn leaf_count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Inner(_, _, _, _, children) => children.iter().map(|c| c.leaf_count()).sum(),
+        }
+    }
+    /// Returns the total capacity of all blocks below the node.
+    pub fn capacity(&self) -> usize {
+        match self {
+            Node::Leaf(block) => block.capacity(),
+            Node::Inner(_, _, _, _, children) => children.iter().map(|c| c.capacity()).sum(),
+        }
+    }
+    /// Shrinks the capacities of all blocks as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        match self {
+            Node::Leaf(block) => block.shrink_to_fit(),
+            Node::Inner(_, _, _, _, children) => {
+                children.iter_mut().for_each(|c| c.shrink_to_fit())
+            }
+        }
+    }
+    /// Grows the capacity of the last block towards `B` for up to `additional` elements,
+    /// returning how many could not be reserved.
+    pub fn reserve_back(&mut self, additional: usize) -> usize {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => {
+                let extra = B.saturating_sub(block.len()).min(additional);
+                block.reserve_exact(extra);
+                additional - extra
+            }
+            Node::Inner(_, _, _, _, children) => children[RIGHT].reserve_back(additional),
+        }
+    }
+    /// Estimates the number of bytes allocated below the node
+    /// (boxed children and blocks capacities).
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            Node::Leaf(block) => block.capacity() * core::mem::size_of::<C>(),
+            Node::Inner(_, _, _, _, children) => children
+                .iter()
+                .map(|c| core::mem::size_of::<Self>() + c.memory_usage())
+                .sum(),
+        }
+    }
+    /// Checks priorities never increase when going down.
+    pub fn is_heap_ordered(&self) -> bool {
         match self {
             Node::Leaf(_) => true,
-            _ => false,
+            Node::Inner(priority, _, _, _, children) => children
+                .iter()
+                .all(|child| child.priority() <= *priority && child.is_heap_ordered()),
+        }
+    }
+    /// Returns the number of inner nodes below (and including) this one
+    /// which share the priority of their inner father.
+    pub fn priority_collisions(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Inner(priority, _, _, _, children) => children
+                .iter()
+                .map(|child| {
+                    let tie = !child.is_leaf() && child.priority() == *priority;
+                    usize::from(tie) + child.priority_collisions()
+                })
+                .sum(),
         }
     }
     pub fn len(&self) -> usize {
         match self {
             Node::Leaf(block) => block.len(),
-            Node::Inner(_, size, _) => *size,
+            Node::Inner(_, size, _, _, _) => *size,
+        }
+    }
+    /// Returns the first element, going down the leftmost path.
+    pub fn first(&self) -> Option<&C> {
+        self.extremity(LEFT)
+    }
+    /// Returns the last element, going down the rightmost path.
+    pub fn last(&self) -> Option<&C> {
+        self.extremity(RIGHT)
+    }
+    /// Returns the element at the extremity of given side.
+    fn extremity(&self, side: usize) -> Option<&C> {
+        match self {
+            Node::Leaf(block) if side == LEFT => block.first(),
+            Node::Leaf(block) => block.last(),
+            Node::Inner(_, _, _, reversed, children) => {
+                let side = if *reversed { 1 - side } else { side };
+                children[side].extremity(side)
+            }
+        }
+    }
+    /// Mutably borrows the first element, going down the leftmost path.
+    pub fn first_mut(&mut self) -> Option<&mut C> {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => block.first_mut(),
+            Node::Inner(_, _, _, _, children) => children[LEFT].first_mut(),
+        }
+    }
+    /// Mutably borrows the last element, going down the rightmost path.
+    pub fn last_mut(&mut self) -> Option<&mut C> {
+        self.push_down();
+        match self {
+            Node::Leaf(block) => block.last_mut(),
+            Node::Inner(_, _, _, _, children) => children[RIGHT].last_mut(),
+        }
+    }
+    /// Returns the index of the first element for which the predicate is false
+    /// (all elements for which it is true being first).
+    pub fn partition_point<P: FnMut(&C) -> bool>(&self, mut pred: P) -> usize {
+        let mut node = self;
+        let mut start = 0;
+        // are we below an odd number of reversed nodes
+        let mut reversed = false;
+        loop {
+            match node {
+                Node::Leaf(block) if reversed => {
+                    return start + block.len() - block.partition_point(|e| !pred(e))
+                }
+                Node::Leaf(block) => return start + block.partition_point(pred),
+                Node::Inner(_, _, _, node_reversed, children) => {
+                    reversed ^= *node_reversed;
+                    let [first, second] = if reversed {
+                        [&children[RIGHT], &children[LEFT]]
+                    } else {
+                        [&children[LEFT], &children[RIGHT]]
+                    };
+                    let second_start = if reversed {
+                        second.last()
+                    } else {
+                        second.first()
+                    };
+                    if second_start.map(&mut pred).unwrap_or(false) {
+                        start += first.len();
+                        node = second;
+                    } else {
+                        node = first;
+                    }
+                }
+            }
         }
     }
     pub fn get(&self, index: usize) -> Option<&C> {
         match self {
             Node::Leaf(block) => block.get(index),
-            Node::Inner(_, _, children) => {
+            Node::Inner(_, size, _, reversed, children) => {
+                if index >= *size {
+                    return None;
+                }
+                let index = if *reversed { size - 1 - index } else { index };
                 let left_size = children[LEFT].len();
-                if left_size >= index {
+                if index < left_size {
                     children[LEFT].get(index)
                 } else {
                     children[RIGHT].get(index - left_size)
@@ -122,11 +674,12 @@ impl<C> Node<C> {
         }
     }
     pub fn get_mut(&mut self, index: usize) -> Option<&mut C> {
+        self.push_down();
         match self {
             Node::Leaf(block) => block.get_mut(index),
-            Node::Inner(_, _, children) => {
+            Node::Inner(_, _, _, _, children) => {
                 let left_size = children[LEFT].len();
-                if left_size >= index {
+                if index < left_size {
                     children[LEFT].get_mut(index)
                 } else {
                     children[RIGHT].get_mut(index - left_size)
@@ -134,4 +687,58 @@ impl<C> Node<C> {
             }
         }
     }
+    /// Mutably borrows all elements at given (sorted, distinct, in bounds) indices,
+    /// storing each of them in `borrows` at the slot paired with its index.
+    /// The tree is traversed only once.
+    pub fn get_disjoint_mut<'a>(
+        &'a mut self,
+        requests: &mut [(usize, usize)],
+        borrows: &mut [Option<&'a mut C>],
+    ) {
+        if requests.is_empty() {
+            return;
+        }
+        self.push_down();
+        match self {
+            Node::Leaf(block) => {
+                let mut pending = requests.iter().peekable();
+                for (index, element) in block.iter_mut().enumerate() {
+                    match pending.peek() {
+                        Some(&&(wanted, slot)) if wanted == index => {
+                            borrows[slot] = Some(element);
+                            pending.next();
+                        }
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+            }
+            Node::Inner(_, _, _, _, children) => {
+                let left_size = children[LEFT].len();
+                let split = requests.partition_point(|&(index, _)| index < left_size);
+                let (left_requests, right_requests) = requests.split_at_mut(split);
+                right_requests
+                    .iter_mut()
+                    .for_each(|(index, _)| *index -= left_size);
+                let [left, right] = children;
+                left.get_disjoint_mut(left_requests, borrows);
+                right.get_disjoint_mut(right_requests, borrows);
+            }
+        }
+    }
+}
+
+/// Puts back children extracted in given direction into left to right order.
+pub(super) fn oriented<T>(mut children: [T; 2], direction: usize) -> [T; 2] {
+    if direction == RIGHT {
+        children.swap(0, 1)
+    }
+    children
+}
+
+/// Summarizes all elements of given block.
+pub(super) fn fold_block<C, M: Monoid<C>>(block: &[C]) -> M {
+    block
+        .iter()
+        .fold(M::identity(), |summary, e| summary.combine(&M::lift(e)))
 }
@@ -0,0 +1,263 @@
+use super::treap::fresh_seed;
+use super::{oriented, ITreap, Node, Priority, BLOCK_SIZE, LEFT, RIGHT};
+use alloc::{rc::Rc, vec, vec::Vec};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Nodes of persistent treaps : children are shared between versions.
+#[derive(Clone)]
+pub(super) enum PersistentNode<C, const B: usize> {
+    Leaf(Vec<C>),
+    /// Inner nodes record their priority and their size.
+    Inner(Priority, usize, [Rc<PersistentNode<C, B>>; 2]),
+}
+
+impl<C: Clone, const B: usize> PersistentNode<C, B> {
+    fn inner(priority: Priority, children: [Rc<Self>; 2]) -> Self {
+        let size = children[LEFT].len() + children[RIGHT].len();
+        PersistentNode::Inner(priority, size, children)
+    }
+    fn len(&self) -> usize {
+        match self {
+            PersistentNode::Leaf(block) => block.len(),
+            PersistentNode::Inner(_, size, _) => *size,
+        }
+    }
+    fn priority(&self) -> Priority {
+        match self {
+            PersistentNode::Leaf(_) => Priority::MIN,
+            PersistentNode::Inner(priority, _, _) => *priority,
+        }
+    }
+    fn extract_content(self, direction: usize) -> (Priority, [Rc<Self>; 2]) {
+        match self {
+            PersistentNode::Leaf(_) => panic!("extracting children from a leaf"),
+            PersistentNode::Inner(priority, _, children) => {
+                (priority, oriented(children, direction))
+            }
+        }
+    }
+    /// Same rotation as for `Node`, only copying nodes shared with other versions.
+    fn rotate(&mut self, direction: usize) {
+        let owned_self = core::mem::replace(self, PersistentNode::Leaf(Vec::new()));
+        let (self_priority, [n1, n2]) = owned_self.extract_content(direction);
+        let (n2_priority, [n3, n4]) = Rc::unwrap_or_clone(n2).extract_content(direction);
+        let new_self = Rc::new(PersistentNode::inner(
+            self_priority,
+            oriented([n1, n3], direction),
+        ));
+        *self = PersistentNode::inner(n2_priority, oriented([new_self, n4], direction))
+    }
+    fn insert<R: Rng + ?Sized>(&mut self, index: usize, element: C, rng: &mut R) {
+        if let PersistentNode::Leaf(block) = self {
+            if block.len() == B {
+                let right_block = block.split_off(B / 2);
+                let left_block = core::mem::take(block);
+                *self = PersistentNode::inner(
+                    rng.gen(),
+                    [
+                        Rc::new(PersistentNode::Leaf(left_block)),
+                        Rc::new(PersistentNode::Leaf(right_block)),
+                    ],
+                );
+            }
+        }
+        match self {
+            PersistentNode::Leaf(block) => block.insert(index, element),
+            PersistentNode::Inner(priority, size, children) => {
+                *size += 1;
+                let left_size = children[LEFT].len();
+                let (direction, remaining_index) = if left_size >= index {
+                    (LEFT, index)
+                } else {
+                    (RIGHT, index - left_size)
+                };
+                Rc::make_mut(&mut children[direction]).insert(remaining_index, element, rng);
+                if children[direction].priority() > *priority {
+                    self.rotate(1 - direction)
+                }
+            }
+        }
+    }
+    fn remove(&mut self, index: usize) -> C {
+        match self {
+            PersistentNode::Leaf(block) => block.remove(index),
+            PersistentNode::Inner(_, size, children) => {
+                *size -= 1;
+                let left_size = children[LEFT].len();
+                let (direction, remaining_index) = if index < left_size {
+                    (LEFT, index)
+                } else {
+                    (RIGHT, index - left_size)
+                };
+                let removed = Rc::make_mut(&mut children[direction]).remove(remaining_index);
+                if children[direction].len() == 0 {
+                    let other = core::mem::replace(
+                        &mut children[1 - direction],
+                        Rc::new(PersistentNode::Leaf(Vec::new())),
+                    );
+                    *self = Rc::unwrap_or_clone(other);
+                }
+                removed
+            }
+        }
+    }
+    fn get(&self, index: usize) -> Option<&C> {
+        match self {
+            PersistentNode::Leaf(block) => block.get(index),
+            PersistentNode::Inner(_, _, children) => {
+                let left_size = children[LEFT].len();
+                if index < left_size {
+                    children[LEFT].get(index)
+                } else {
+                    children[RIGHT].get(index - left_size)
+                }
+            }
+        }
+    }
+    /// Converts a node of an ordinary treap, applying pending reversals.
+    fn from_node(mut node: Node<C, B>) -> Self {
+        node.push_down();
+        match node {
+            Node::Leaf(block) => PersistentNode::Leaf(block),
+            Node::Inner(priority, size, _, _, [left, right]) => PersistentNode::Inner(
+                priority,
+                size,
+                [
+                    Rc::new(PersistentNode::from_node(*left)),
+                    Rc::new(PersistentNode::from_node(*right)),
+                ],
+            ),
+        }
+    }
+}
+
+/// An indexed treap whose versions share structure.
+/// Cloning is O(1) and modifications only copy the nodes on the path they go through,
+/// leaving all other versions untouched.
+/// This makes it cheap to keep old versions around, for example for undoing.
+///
+/// # Example
+///
+/// ```
+/// use itreap::PersistentITreap;
+///
+/// let mut t: PersistentITreap<_> = (0..10).collect();
+/// let snapshot = t.clone();
+/// t.remove(0);
+/// t.insert(3, 42);
+/// assert!(snapshot.iter().copied().eq(0..10));
+/// assert_eq!(t.get(3), Some(&42));
+/// ```
+#[derive(Clone)]
+pub struct PersistentITreap<C, const B: usize = BLOCK_SIZE> {
+    pub(super) root: Rc<PersistentNode<C, B>>,
+    rng: SmallRng,
+}
+
+impl<C: Clone, const B: usize> Default for PersistentITreap<C, B> {
+    fn default() -> Self {
+        let () = Node::<C, B>::VALID_BLOCK_SIZE;
+        PersistentITreap {
+            root: Rc::new(PersistentNode::Leaf(Vec::new())),
+            rng: SmallRng::seed_from_u64(fresh_seed()),
+        }
+    }
+}
+
+impl<C: Clone> PersistentITreap<C> {
+    /// Creates a new empty persistent treap.
+    pub fn new() -> Self {
+        PersistentITreap::default()
+    }
+}
+
+impl<C: Clone, const B: usize> PersistentITreap<C, B> {
+    /// Returns the number of elements.
+    /// Cost is O(1).
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+    /// Returns `true` if there are no elements.
+    /// Cost is O(1).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Borrows the element at position `index`, or returns `None` if out of bounds.
+    /// Cost is O(log(n/B)).
+    pub fn get(&self, index: usize) -> Option<&C> {
+        self.root.get(index)
+    }
+    /// Inserts given element at given index, copying the nodes
+    /// on the way down if they are shared with other versions.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, element: C) {
+        assert!(index <= self.len(), "insertion index out of bounds");
+        Rc::make_mut(&mut self.root).insert(index, element, &mut self.rng)
+    }
+    /// Removes and returns the element at given index, copying the nodes
+    /// on the way down if they are shared with other versions.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> C {
+        assert!(index < self.len(), "removal index out of bounds");
+        Rc::make_mut(&mut self.root).remove(index)
+    }
+    /// Loops on all elements in order.
+    /// Cost is O(n).
+    pub fn iter(&self) -> impl Iterator<Item = &C> {
+        let mut remaining_nodes = vec![self.root.as_ref()];
+        core::iter::from_fn(move || {
+            while let Some(node) = remaining_nodes.pop() {
+                match node {
+                    PersistentNode::Inner(_, _, [left, right]) => {
+                        remaining_nodes.push(right);
+                        remaining_nodes.push(left);
+                    }
+                    PersistentNode::Leaf(block) => return Some(block.iter()),
+                }
+            }
+            None
+        })
+        .flatten()
+    }
+    /// Lists the addresses of all nodes, to check sharing between versions.
+    #[cfg(test)]
+    pub(super) fn node_addresses(&self) -> Vec<*const PersistentNode<C, B>> {
+        let mut addresses = Vec::new();
+        let mut remaining_nodes = vec![&self.root];
+        while let Some(node) = remaining_nodes.pop() {
+            addresses.push(Rc::as_ptr(node));
+            if let PersistentNode::Inner(_, _, children) = node.as_ref() {
+                remaining_nodes.extend(children.iter());
+            }
+        }
+        addresses
+    }
+}
+
+impl<C: Clone, const B: usize> From<ITreap<C, B>> for PersistentITreap<C, B> {
+    /// Converts an ordinary indexed treap, keeping its structure.
+    /// Cost is O(n/B).
+    fn from(mut treap: ITreap<C, B>) -> Self {
+        let root = core::mem::replace(&mut treap.root, Node::Leaf(Vec::new()));
+        PersistentITreap {
+            root: Rc::new(PersistentNode::from_node(root)),
+            rng: SmallRng::seed_from_u64(fresh_seed()),
+        }
+    }
+}
+
+impl<C: Clone, const B: usize> core::iter::FromIterator<C> for PersistentITreap<C, B> {
+    /// Builds a balanced persistent treap.
+    /// Cost is O(n).
+    fn from_iter<T: IntoIterator<Item = C>>(iter: T) -> Self {
+        iter.into_iter().collect::<ITreap<C, B>>().into()
+    }
+}
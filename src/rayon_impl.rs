@@ -0,0 +1,154 @@
+use super::{ITreap, Iter, Monoid, Node, BLOCK_SIZE, LEFT, RIGHT};
+use core::ops::Range;
+use rayon::iter::plumbing::{
+    bridge, bridge_unindexed, Consumer, Folder, Producer, ProducerCallback, UnindexedConsumer,
+    UnindexedProducer,
+};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+/// A parallel iterator on all elements of an indexed treap.
+/// Work is split along the tree : each task holds the lowest subtree
+/// covering its range of indices and splits it between its two children.
+pub struct ParIter<'a, C, const B: usize = BLOCK_SIZE, M = ()> {
+    node: &'a Node<C, B, M>,
+    /// Range of indices in the sequence of `node`.
+    range: Range<usize>,
+    /// Whether `node` lies below an odd number of pending reversals.
+    reversed: bool,
+}
+
+impl<C: Sync, const B: usize, M: Monoid<C> + Sync> ITreap<C, B, M> {
+    /// Loops in parallel on all elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    /// use rayon::prelude::*;
+    ///
+    /// let t: ITreap<u64> = (0..10_000).collect();
+    /// assert_eq!(t.par_iter().sum::<u64>(), 49_995_000);
+    /// ```
+    pub fn par_iter(&self) -> ParIter<'_, C, B, M> {
+        ParIter {
+            node: &self.root,
+            range: 0..self.len(),
+            reversed: false,
+        }
+        .descend()
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> ParIter<'a, C, B, M> {
+    /// Returns the children of the node in sequence order
+    /// and whether they lie below an odd number of pending reversals,
+    /// or `None` on leaves.
+    fn children(&self) -> Option<([&'a Node<C, B, M>; 2], bool)> {
+        match self.node {
+            Node::Leaf(_) => None,
+            Node::Inner(_, _, _, node_reversed, children) => {
+                let reversed = self.reversed ^ node_reversed;
+                let [left, right]: [&'a Node<C, B, M>; 2] = [&children[LEFT], &children[RIGHT]];
+                Some((
+                    if reversed {
+                        [right, left]
+                    } else {
+                        [left, right]
+                    },
+                    reversed,
+                ))
+            }
+        }
+    }
+    /// Goes down to the lowest subtree covering the whole range.
+    fn descend(mut self) -> Self {
+        while let Some(([first, second], reversed)) = self.children() {
+            let boundary = first.len();
+            if self.range.end <= boundary {
+                self.node = first;
+            } else if self.range.start >= boundary {
+                self.node = second;
+                self.range = (self.range.start - boundary)..(self.range.end - boundary);
+            } else {
+                break;
+            }
+            self.reversed = reversed;
+        }
+        self
+    }
+}
+
+impl<'a, C: Sync, const B: usize, M: Monoid<C> + Sync> ParallelIterator for ParIter<'a, C, B, M> {
+    type Item = &'a C;
+    fn drive_unindexed<S: UnindexedConsumer<Self::Item>>(self, consumer: S) -> S::Result {
+        bridge_unindexed(self, consumer)
+    }
+    /// Unindexed consumers get tasks split between children,
+    /// which is only allowed if we do not advertise our length here.
+    /// Indexed adaptors still know it through `IndexedParallelIterator::len`.
+    fn opt_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<'a, C: Sync, const B: usize, M: Monoid<C> + Sync> IndexedParallelIterator
+    for ParIter<'a, C, B, M>
+{
+    fn len(&self) -> usize {
+        self.range.len()
+    }
+    fn drive<S: Consumer<Self::Item>>(self, consumer: S) -> S::Result {
+        bridge(self, consumer)
+    }
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(self)
+    }
+}
+
+impl<'a, C: Sync, const B: usize, M: Monoid<C> + Sync> UnindexedProducer for ParIter<'a, C, B, M> {
+    type Item = &'a C;
+    /// Splits between the two children, leaves are not split.
+    fn split(self) -> (Self, Option<Self>) {
+        match self.children() {
+            Some(([first, _], _)) => {
+                let index = first.len() - self.range.start;
+                let (left, right) = Producer::split_at(self, index);
+                (left, Some(right))
+            }
+            None => (self, None),
+        }
+    }
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        folder.consume_iter(Producer::into_iter(self))
+    }
+}
+
+impl<'a, C: Sync, const B: usize, M: Monoid<C> + Sync> Producer for ParIter<'a, C, B, M> {
+    type Item = &'a C;
+    type IntoIter = Iter<'a, C, B, M>;
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::below(self.node, self.range, self.reversed)
+    }
+    /// Tasks smaller than a block are not worth it.
+    fn min_len(&self) -> usize {
+        B
+    }
+    /// Splits the range, each side then going down to its own subtree.
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let middle = self.range.start + index;
+        (
+            ParIter {
+                node: self.node,
+                range: self.range.start..middle,
+                reversed: self.reversed,
+            }
+            .descend(),
+            ParIter {
+                node: self.node,
+                range: middle..self.range.end,
+                reversed: self.reversed,
+            }
+            .descend(),
+        )
+    }
+}
@@ -0,0 +1,70 @@
+use super::treap::{oriented_blocks, oriented_iter};
+use super::{ITreap, Monoid};
+
+/// A read-only view of an indexed treap in reverse order.
+/// Indexing the view does not require reversing the treap.
+///
+/// # Example
+///
+/// ```
+/// use itreap::ITreap;
+///
+/// let t: ITreap<_> = (0..10).collect();
+/// let view = t.reversed_view();
+/// assert_eq!(view[0], 9);
+/// assert_eq!(view[9], 0);
+/// ```
+pub struct ReversedView<'a, C, const B: usize, M> {
+    treap: &'a ITreap<C, B, M>,
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> ReversedView<'a, C, B, M> {
+    /// Returns the number of elements in the view.
+    /// Cost is O(1).
+    pub fn len(&self) -> usize {
+        self.treap.len()
+    }
+    /// Returns true if there are no elements in the view.
+    /// Cost is O(1).
+    pub fn is_empty(&self) -> bool {
+        self.treap.is_empty()
+    }
+    /// Loops on all elements of the view (the treap's elements in reverse order).
+    /// Cost is O(n).
+    pub fn iter(&self) -> impl Iterator<Item = &'a C> + 'a {
+        self.treap.reversed()
+    }
+}
+
+impl<'a, C, const B: usize, M: Monoid<C>> core::ops::Index<usize> for ReversedView<'a, C, B, M> {
+    type Output = C;
+    /// Borrows the `i`th element from the back of the treap.
+    /// Cost is O(log(n/B)).
+    fn index(&self, i: usize) -> &Self::Output {
+        assert!(i < self.len(), "index out of bounds");
+        &self.treap[self.len() - 1 - i]
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> ITreap<C, B, M> {
+    /// Loops on all elements, from last to first.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..4).collect();
+    /// assert!(t.reversed().eq(&[3, 2, 1, 0]));
+    /// ```
+    pub fn reversed<'a>(&'a self) -> impl Iterator<Item = &'a C> + 'a {
+        oriented_blocks(&self.root, true)
+            .flat_map(|(block, reversed)| oriented_iter(block, reversed))
+    }
+    /// Returns a view of the treap in reverse order, supporting indexing.
+    /// Cost is O(1).
+    pub fn reversed_view(&self) -> ReversedView<'_, C, B, M> {
+        ReversedView { treap: self }
+    }
+}
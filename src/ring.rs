@@ -0,0 +1,68 @@
+use super::{ITreap, BLOCK_SIZE};
+
+/// A sliding window over the most recent elements pushed.
+/// Once `capacity` is reached, pushing an element evicts the oldest one.
+///
+/// # Example
+///
+/// ```
+/// use itreap::RingTreap;
+///
+/// let mut r: RingTreap<_> = RingTreap::new(2);
+/// assert_eq!(r.push(1), None);
+/// assert_eq!(r.push(2), None);
+/// assert_eq!(r.push(3), Some(1));
+/// assert!(r.iter().eq(&[2, 3]));
+/// ```
+pub struct RingTreap<C, const B: usize = BLOCK_SIZE> {
+    treap: ITreap<C, B>,
+    capacity: usize,
+}
+
+impl<C, const B: usize> RingTreap<C, B> {
+    /// Creates a new empty ring holding at most `capacity` elements.
+    pub fn new(capacity: usize) -> Self {
+        RingTreap {
+            treap: ITreap::default(),
+            capacity,
+        }
+    }
+    /// Returns the maximal number of elements in the ring.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Returns the number of elements in the ring.
+    /// Cost is O(1).
+    pub fn len(&self) -> usize {
+        self.treap.len()
+    }
+    /// Returns `true` if the ring contains no elements.
+    /// Cost is O(1).
+    pub fn is_empty(&self) -> bool {
+        self.treap.is_empty()
+    }
+    /// Adds an element to the back, evicting and returning
+    /// the front element if the ring is full.
+    /// Cost is O(log(n/B)+B).
+    pub fn push(&mut self, element: C) -> Option<C> {
+        if self.capacity == 0 {
+            return Some(element);
+        }
+        let evicted = if self.treap.len() == self.capacity {
+            self.treap.pop_front()
+        } else {
+            None
+        };
+        self.treap.push(element);
+        evicted
+    }
+    /// Loops on all elements, from oldest to most recent.
+    /// Cost is O(n).
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a C> + 'a {
+        self.treap.iter()
+    }
+    /// Borrows the underlying indexed treap.
+    pub fn as_treap(&self) -> &ITreap<C, B> {
+        &self.treap
+    }
+}
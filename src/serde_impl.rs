@@ -0,0 +1,43 @@
+use super::{ITreap, Monoid};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+impl<C: Serialize, const B: usize, M: Monoid<C>> Serialize for ITreap<C, B, M> {
+    /// Serializes the treap as a flat sequence of elements.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for element in self.iter() {
+            seq.serialize_element(element)?;
+        }
+        seq.end()
+    }
+}
+
+/// Collects a sequence of elements into a balanced treap.
+struct SeqVisitor<C, const B: usize, M>(PhantomData<(C, M)>);
+
+impl<'de, C: Deserialize<'de>, const B: usize, M: Monoid<C>> Visitor<'de> for SeqVisitor<C, B, M> {
+    type Value = ITreap<C, B, M>;
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a sequence")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // the size hint comes from the input : don't let it allocate more than a megabyte
+        let max_preallocation = (1 << 20) / core::mem::size_of::<C>().max(1);
+        let capacity = seq.size_hint().unwrap_or(0).min(max_preallocation);
+        let mut elements = Vec::with_capacity(capacity);
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(elements.into_iter().collect())
+    }
+}
+
+impl<'de, C: Deserialize<'de>, const B: usize, M: Monoid<C>> Deserialize<'de> for ITreap<C, B, M> {
+    /// Deserializes a flat sequence of elements into a balanced treap.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
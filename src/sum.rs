@@ -0,0 +1,104 @@
+use super::{ITreap, Node, Sum, LEFT, RIGHT};
+use core::ops::{Add, Range};
+use rand::{distributions::uniform::SampleUniform, Rng};
+
+impl<C: Add<Output = C> + Default + Copy, const B: usize> ITreap<C, B, Sum<C>> {
+    /// Returns the sum of all elements.
+    /// Cost is O(B).
+    pub fn sum(&self) -> C {
+        self.root.summary().0
+    }
+    /// Returns the sum of all elements in given range of indices.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or goes past the end.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::SumTreap;
+    ///
+    /// let t: SumTreap<_> = (0..10).collect();
+    /// assert_eq!(t.range_sum(2..5), 9);
+    /// ```
+    pub fn range_sum(&self, range: Range<usize>) -> C {
+        self.range_fold(range).0
+    }
+    /// Finds the first element for which the sum of all elements up to it
+    /// (itself included) is above `target`, returning it with its index.
+    /// Elements are assumed to be non-negative.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::SumTreap;
+    ///
+    /// let t: SumTreap<_> = vec![3, 1, 2].into_iter().collect();
+    /// assert_eq!(t.find_by_prefix_sum(0), Some((0, &3)));
+    /// assert_eq!(t.find_by_prefix_sum(3), Some((1, &1)));
+    /// assert_eq!(t.find_by_prefix_sum(5), Some((2, &2)));
+    /// assert_eq!(t.find_by_prefix_sum(6), None);
+    /// ```
+    pub fn find_by_prefix_sum(&self, target: C) -> Option<(usize, &C)>
+    where
+        C: PartialOrd,
+    {
+        let mut node = &self.root;
+        let mut index = 0;
+        let mut before = C::default();
+        loop {
+            match node {
+                Node::Leaf(block) => {
+                    return block.iter().enumerate().find_map(|(i, e)| {
+                        before = before + *e;
+                        if before > target {
+                            Some((index + i, e))
+                        } else {
+                            None
+                        }
+                    })
+                }
+                Node::Inner(_, _, _, _, children) => {
+                    let with_left = before + children[LEFT].summary().0;
+                    if with_left > target {
+                        node = &children[LEFT];
+                    } else {
+                        before = with_left;
+                        index += children[LEFT].len();
+                        node = &children[RIGHT];
+                    }
+                }
+            }
+        }
+    }
+    /// Considers elements as (non-negative) weights, randomly picks one
+    /// with a probability proportional to its weight and removes it.
+    /// Repeated calls therefore sample without replacement.
+    /// Returns `None` if the treap is empty or if all weights are zero.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::SumTreap;
+    ///
+    /// let mut t: SumTreap<_> = vec![0, 5, 0].into_iter().collect();
+    /// assert_eq!(t.sample_weighted(&mut rand::thread_rng()), Some(5));
+    /// assert_eq!(t.sample_weighted(&mut rand::thread_rng()), None);
+    /// ```
+    pub fn sample_weighted<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<C>
+    where
+        C: PartialOrd + SampleUniform,
+    {
+        let total = self.sum();
+        if total.partial_cmp(&C::default()) != Some(core::cmp::Ordering::Greater) {
+            return None;
+        }
+        let target = rng.gen_range(C::default()..total);
+        let (index, _) = self.find_by_prefix_sum(target)?;
+        Some(self.root.remove(index))
+    }
+}
@@ -1,13 +1,43 @@
-use super::{Node, Priority, BLOCK_SIZE};
+use super::{Iter, IterMut, Monoid, Node, Priority, Sum, TotalWeight, BLOCK_SIZE, LEFT, RIGHT};
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::ops::Range;
 use itertools::Itertools;
-use rand::random;
-use std::ops::Range;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
 
-pub struct ITreap<C> {
-    root: Node<C>,
+/// An indexed treap : a sequence of elements stored in blocks of at most `B` elements.
+/// Each inner node caches a summary of type `M` of all its elements (nothing by default).
+/// Blocks must hold at least two elements, smaller sizes do not compile.
+///
+/// ```compile_fail
+/// use itreap::ITreap;
+///
+/// let t: ITreap<u32, 1> = ITreap::default();
+/// ```
+#[derive(Clone)]
+pub struct ITreap<C, const B: usize = BLOCK_SIZE, M = ()> {
+    pub(super) root: Node<C, B, M>,
+    /// Generates priorities for all nodes created by this treap.
+    rng: SmallRng,
+    /// Empty blocks planned by `with_capacity` and `reserve`,
+    /// becoming new last leaves when pushing past a full one.
+    spare_blocks: Vec<Vec<C>>,
 }
 
-impl<C> std::ops::Index<usize> for ITreap<C> {
+/// A saved state of an indexed treap, see `ITreap::snapshot`.
+pub struct Snapshot<C, const B: usize = BLOCK_SIZE, M = ()> {
+    treap: ITreap<C, B, M>,
+}
+
+/// An indexed treap maintaining sums of elements.
+pub type SumTreap<C, const B: usize = BLOCK_SIZE> = ITreap<C, B, Sum<C>>;
+
+/// An indexed treap maintaining total weights of elements.
+pub type WeightedTreap<C, const B: usize = BLOCK_SIZE> = ITreap<C, B, TotalWeight>;
+
+/// Emptied node boxes, kept around for building a new tree.
+type SpareBoxes<C, const B: usize, M> = Vec<Box<Node<C, B, M>>>;
+
+impl<C, const B: usize, M: Monoid<C>> core::ops::Index<usize> for ITreap<C, B, M> {
     type Output = C;
     /// Borrows the `i`th element.
     /// Cost is O(log(n/B)).
@@ -16,7 +46,7 @@ impl<C> std::ops::Index<usize> for ITreap<C> {
     }
 }
 
-impl<C> std::ops::IndexMut<usize> for ITreap<C> {
+impl<C, const B: usize> core::ops::IndexMut<usize> for ITreap<C, B> {
     /// Mutably borrows the `i`th element.
     /// Cost is O(log(n/B)).
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
@@ -26,14 +56,379 @@ impl<C> std::ops::IndexMut<usize> for ITreap<C> {
 
 impl<C> ITreap<C> {
     /// Create a new empty indexed treap.
+    /// Use `ITreap::default()` for block sizes other than the default one.
     pub fn new() -> Self {
+        ITreap::default()
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> ITreap<C, B, M> {
+    /// Builds a balanced indexed treap from `(count, value)` pairs,
+    /// each value being repeated `count` times.
+    /// Cost is O(n) where n is the total count.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = ITreap::from_runs(vec![(2, 'a'), (0, 'b'), (1, 'c')]);
+    /// assert!(t.iter().eq(&['a', 'a', 'c']));
+    /// ```
+    pub fn from_runs<I: IntoIterator<Item = (usize, C)>>(runs: I) -> Self
+    where
+        C: Clone,
+    {
+        let mut blocks = Vec::new();
+        let mut current_block = Vec::with_capacity(B / 2);
+        for (mut count, value) in runs {
+            while count > 0 {
+                let taken = count.min(B / 2 - current_block.len());
+                current_block.resize(current_block.len() + taken, value.clone());
+                count -= taken;
+                if current_block.len() == B / 2 {
+                    blocks.push(core::mem::replace(
+                        &mut current_block,
+                        Vec::with_capacity(B / 2),
+                    ));
+                }
+            }
+        }
+        if !current_block.is_empty() {
+            blocks.push(current_block);
+        }
+        let mut treap = ITreap::default();
+        treap.root = build_from_blocks(blocks.into_iter(), &mut treap.rng);
+        debug_assert!(treap.is_valid());
+        treap
+    }
+    /// Builds a treap from an iterator exactly like `collect` does
+    /// but skips the final validity check of debug builds.
+    /// This is meant for huge trusted inputs where the check would dominate.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = ITreap::from_iter_unchecked(0..10);
+    /// assert!(t.iter().copied().eq(0..10));
+    /// ```
+    pub fn from_iter_unchecked<I: IntoIterator<Item = C>>(iter: I) -> Self {
+        let mut treap = ITreap::default();
+        treap.rebuild(iter);
+        treap
+    }
+    /// Creates an empty treap whose priorities are drawn from a generator
+    /// seeded with given seed. Treaps with the same seed going through
+    /// the same sequence of operations end up with the same structure.
+    /// Cost is O(1).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<u32> = ITreap::with_seed(42);
+    /// t.push(1);
+    /// assert_eq!(t.len(), 1);
+    /// ```
+    pub fn with_seed(seed: u64) -> Self {
+        let () = Node::<C, B, M>::VALID_BLOCK_SIZE;
         ITreap {
             root: Node::Leaf(Vec::new()),
+            rng: SmallRng::seed_from_u64(seed),
+            spare_blocks: Vec::new(),
         }
     }
+    /// Creates an empty treap ready to receive `n` elements.
+    /// All `n / B` blocks are allocated upfront : the first one is the root leaf
+    /// and the others are kept aside, each becoming the new last leaf when pushing
+    /// past a full one. So pushing `n` elements never reallocates.
+    /// Cost is O(n/B) (allocations only).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<u32> = ITreap::with_capacity(10);
+    /// assert!(t.is_empty());
+    /// assert!(t.capacity() >= 10);
+    /// ```
+    pub fn with_capacity(n: usize) -> Self {
+        let mut treap = ITreap::from_root(Node::Leaf(Vec::with_capacity(n.min(B))));
+        treap.plan_blocks(n.saturating_sub(B));
+        treap
+    }
+    /// Allocates enough spare blocks for `additional` elements,
+    /// counting the spare blocks we already have.
+    fn plan_blocks(&mut self, additional: usize) {
+        let planned = self.spare_blocks.len() * B;
+        let missing_blocks = additional.saturating_sub(planned).div_ceil(B);
+        self.spare_blocks
+            .extend(core::iter::repeat_with(|| Vec::with_capacity(B)).take(missing_blocks));
+    }
+    /// Builds a treap from an iterator like `collect` but with a
+    /// deterministic structure, see `with_seed`.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = ITreap::from_iter_with_seed(0..10, 42);
+    /// assert!(t.iter().copied().eq(0..10));
+    /// ```
+    pub fn from_iter_with_seed<I: IntoIterator<Item = C>>(iter: I, seed: u64) -> Self {
+        let mut treap = ITreap::with_seed(seed);
+        treap.rebuild(iter);
+        debug_assert!(treap.is_valid());
+        treap
+    }
+    /// Replaces all content by given elements, spread directly in their final blocks.
+    /// Cost is O(n).
+    fn rebuild<I: IntoIterator<Item = C>>(&mut self, elements: I) {
+        // avoid inserting elements one by one.
+        // spread all elements directly into their final blocks
+        self.root = build_from_blocks(
+            elements
+                .into_iter()
+                .chunks(B / 2)
+                .into_iter()
+                .map(|chunk| chunk.collect()),
+            &mut self.rng,
+        );
+    }
+    /// Builds a treap from sorted elements, dropping consecutive duplicates.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = ITreap::from_sorted_dedup(vec![1, 1, 2, 3, 3, 3]);
+    /// assert!(t.iter().eq(&[1, 2, 3]));
+    /// ```
+    pub fn from_sorted_dedup<I: IntoIterator<Item = C>>(iter: I) -> Self
+    where
+        C: PartialEq,
+    {
+        iter.into_iter().dedup().collect()
+    }
     /// Checks that the data structure respects its constraints.
     pub(super) fn is_valid(&self) -> bool {
-        self.root.is_valid(None)
+        self.validate().is_ok()
+    }
+    /// Checks that the data structure respects its constraints,
+    /// describing the first violation found (heap order, size mismatch,
+    /// oversized leaf or empty non-root leaf) and where it is located.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10_000).collect();
+    /// assert_eq!(t.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> Result<(), String> {
+        self.root.validate(None, &mut String::new())
+    }
+    /// Checks only the heap order on priorities, ignoring sizes and leaves.
+    /// This is handy to tell balancing bugs apart from accounting bugs.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10_000).collect();
+    /// assert!(t.check_heap_order());
+    /// ```
+    pub fn check_heap_order(&self) -> bool {
+        self.root.is_heap_ordered()
+    }
+    /// Counts inner nodes whose priority is equal to their father's.
+    /// Such ties are legal but the treap loses its balance guarantees
+    /// if they become frequent.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10_000).collect();
+    /// assert_eq!(t.priority_collisions(), 0);
+    /// ```
+    pub fn priority_collisions(&self) -> usize {
+        self.root.priority_collisions()
+    }
+    /// Returns the maximal number of edges from the root down to a leaf.
+    /// For a balanced treap it is close to log2(n/B).
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert_eq!(t.depth(), 0);
+    /// ```
+    pub fn depth(&self) -> usize {
+        self.root.depth()
+    }
+    /// Returns the number of leaf blocks.
+    /// Cost is O(n/B).
+    pub fn leaf_count(&self) -> usize {
+        self.root.leaf_count()
+    }
+    /// Estimates the number of bytes used by the treap,
+    /// counting all nodes and the full capacity of all blocks.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<u64> = (0..10_000).collect();
+    /// assert!(t.memory_usage() >= 10_000 * 8);
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        let spare_usage = self.spare_blocks.capacity() * core::mem::size_of::<Vec<C>>()
+            + self.spare_capacity() * core::mem::size_of::<C>();
+        core::mem::size_of::<Self>() + self.root.memory_usage() + spare_usage
+    }
+    /// Checks that the height of the tree is within `tolerance * log2(n/B)`.
+    #[cfg(test)]
+    pub(super) fn is_balanced(&self, tolerance: f64) -> bool {
+        let blocks = (self.len() as f64 / B as f64).log2().max(1.0);
+        self.root.depth() as f64 <= tolerance * blocks
+    }
+    /// Lists the addresses of all boxed nodes, to check allocation reuse.
+    #[cfg(test)]
+    pub(super) fn node_addresses(&self) -> Vec<*const Node<C, B, M>> {
+        let mut addresses = Vec::new();
+        let mut remaining_nodes = vec![&self.root];
+        while let Some(node) = remaining_nodes.pop() {
+            if let Node::Inner(_, _, _, _, children) = node {
+                addresses.extend(children.iter().map(|child| &**child as *const _));
+                remaining_nodes.extend(children.iter().map(|child| &**child));
+            }
+        }
+        addresses
+    }
+    /// Borrows the first element, or returns `None` if the treap is empty.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.first(), Some(&1));
+    /// assert_eq!(ITreap::<u32>::new().first(), None);
+    /// ```
+    pub fn first(&self) -> Option<&C> {
+        self.root.first()
+    }
+    /// Borrows the last element, or returns `None` if the treap is empty.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.last(), Some(&3));
+    /// ```
+    pub fn last(&self) -> Option<&C> {
+        self.root.last()
+    }
+    /// Borrows the front element, like `VecDeque::front`.
+    /// This is an alias of `first`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.peek_front(), Some(&1));
+    /// assert_eq!(t.peek_back(), Some(&3));
+    /// ```
+    pub fn peek_front(&self) -> Option<&C> {
+        self.first()
+    }
+    /// Borrows the back element, like `VecDeque::back`.
+    /// This is an alias of `last`.
+    pub fn peek_back(&self) -> Option<&C> {
+        self.last()
+    }
+    /// Borrows the element at position `i`, or returns `None` if out of bounds.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.get(2), Some(&3));
+    /// assert_eq!(t.get(3), None);
+    /// ```
+    pub fn get(&self, i: usize) -> Option<&C> {
+        self.root.get(i)
+    }
+    /// Returns a copy of the element at position `i`.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i >= len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.at(0) + t.at(2), 4);
+    /// ```
+    pub fn at(&self, i: usize) -> C
+    where
+        C: Copy,
+    {
+        self[i]
+    }
+    /// Returns a clone of the element at position `i`
+    /// or the default value if `i` is out of range.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..4).collect();
+    /// assert_eq!(t.get_or_default(1), 2);
+    /// assert_eq!(t.get_or_default(10), 0);
+    /// ```
+    pub fn get_or_default(&self, i: usize) -> C
+    where
+        C: Default + Clone,
+    {
+        self.root.get(i).cloned().unwrap_or_default()
     }
     /// Inserts an element at position `index`.
     /// Cost is O(log(n/B)+B).
@@ -52,7 +447,7 @@ impl<C> ITreap<C> {
     /// assert!(t.iter().eq(&[2, 3, 7]))
     /// ```
     pub fn insert(&mut self, index: usize, element: C) {
-        self.root.insert(index, element)
+        self.root.insert(index, element, &mut self.rng)
     }
     /// Adds an element to the back.
     /// Cost is O(log(n/B)+1).
@@ -71,150 +466,2117 @@ impl<C> ITreap<C> {
     /// assert!(t.iter().eq(&[2, 4 ,6]))
     /// ```
     pub fn push(&mut self, element: C) {
+        if self.root.extreme_leaf_len(RIGHT) == B {
+            if let Some(mut block) = self.spare_blocks.pop() {
+                block.push(element);
+                return self.push_block_back(block);
+            }
+        }
         self.insert(self.len(), element)
     }
-    /// Returns the number of elements in the indexed treap.
-    /// Cost is O(1).
-    pub fn len(&self) -> usize {
-        self.root.len()
+    /// Removes the last element and returns it, or `None` if the treap is empty.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// assert_eq!(t.pop(), Some(2));
+    /// assert!(t.iter().eq(&[0, 1]));
+    /// ```
+    pub fn pop(&mut self) -> Option<C> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.root.remove(self.len() - 1))
+        }
     }
-    /// Loops on all elements.
-    /// Cost is O(n).
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a C> + 'a {
-        self.between(0..self.root.len())
+    /// Removes the first element and returns it, or `None` if the treap is empty.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// assert_eq!(t.pop_front(), Some(0));
+    /// assert!(t.iter().eq(&[1, 2]));
+    /// ```
+    pub fn pop_front(&mut self) -> Option<C> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.root.remove(0))
+        }
     }
-    /// Loop on all elements corresponding to indices in given range.
-    /// Cost is O(log(n/B) + k) where k designates the number of elements we should loop upon.
+    /// Removes the element at position `index` and returns it,
+    /// shifting all following elements.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
     ///
     /// # Example
     ///
     /// ```
     /// use itreap::ITreap;
     ///
-    /// let t:ITreap<_> = (0..10).map(|e| e*2).collect();
-    /// assert!(t.between(1..4).eq(&[2, 4, 6]))
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// assert_eq!(t.remove(1), 1);
+    /// assert!(t.iter().eq(&[0, 2, 3, 4]));
     /// ```
-    pub fn between<'a>(
-        &'a self,
-        selection: std::ops::Range<usize>,
-    ) -> impl Iterator<Item = &'a C> + 'a {
-        let mut remaining_nodes = std::iter::once((&self.root, 0..self.root.len()))
-            .filter(|(_, r)| !intersect_ranges(r, &selection).is_empty())
-            .collect::<Vec<_>>();
-        let mut current_block = None;
-        let mut current_block_iter = None;
-        std::iter::from_fn(move || loop {
-            while current_block.is_none() && !remaining_nodes.is_empty() {
-                let (next_node, next_node_range) = remaining_nodes.pop().unwrap();
-                match next_node {
-                    Node::Inner(_, _, [left, right]) => {
-                        let right_start = next_node_range.start + left.len();
-                        let right_range = right_start..next_node_range.end;
-                        let left_range = next_node_range.start..right_start;
-                        if !intersect_ranges(&right_range, &selection).is_empty() {
-                            remaining_nodes.push((right, right_range));
-                        }
-                        if !intersect_ranges(&left_range, &selection).is_empty() {
-                            remaining_nodes.push((left, left_range));
-                        }
-                    }
-                    Node::Leaf(block) => {
-                        current_block = Some(block);
-                        let selected = intersect_ranges(&next_node_range, &selection);
-                        let retained_elements = (selected.start - next_node_range.start)
-                            ..(selected.end - next_node_range.start);
-                        current_block_iter =
-                            current_block.as_ref().map(|b| b[retained_elements].iter())
-                    }
-                }
-            }
-            if let Some(iter) = &mut current_block_iter {
-                let maybe_next_value = iter.next();
-                if let Some(next_value) = maybe_next_value {
-                    return Some(next_value);
-                } else {
-                    current_block_iter = None;
-                    current_block = None;
-                }
-            } else {
-                return None;
-            }
-        })
+    pub fn remove(&mut self, index: usize) -> C {
+        let len = self.len();
+        assert!(
+            index < len,
+            "removal index (is {}) should be < len (is {})",
+            index,
+            len
+        );
+        self.root.remove(index)
     }
-}
-
-impl<C> std::default::Default for ITreap<C> {
-    fn default() -> Self {
-        ITreap::new()
+    /// Swaps the elements at indices `i` and `j`.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` or `j` are out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// t.swap(1, 3);
+    /// assert!(t.iter().eq(&[0, 3, 2, 1, 4]));
+    /// ```
+    pub fn swap(&mut self, i: usize, j: usize) {
+        let len = self.len();
+        for index in [i, j] {
+            assert!(
+                index < len,
+                "index out of bounds: the len is {} but the index is {}",
+                len,
+                index
+            );
+        }
+        if i != j {
+            self.root.swap(i.min(j), i.max(j))
+        }
     }
-}
-
-impl<C> std::iter::FromIterator<C> for ITreap<C> {
-    /// Transform an iterator into an indexed treap.
-    /// This will always create a perfectly balanced tree.
-    /// Cost is O(n).
-    fn from_iter<T: IntoIterator<Item = C>>(iter: T) -> Self {
-        // avoid inserting elements one by one.
-        // spread all elements directly into their final blocks
-        let (mut tree, leaves) = iter.into_iter().chunks(BLOCK_SIZE / 2).into_iter().fold(
-            (Vec::new(), 0),
-            |(mut tree, leaves), chunk| {
-                // we keep a stack of nodes
-                // and merge the last two nodes when the get equal size
-                let block = chunk.collect::<Vec<_>>();
-                tree.push(Box::new(Node::Leaf(block)));
-                loop {
-                    let l = tree.len();
-                    if l >= 2 && tree[l - 1].len() == tree[l - 2].len() {
-                        let right_node = tree.pop().unwrap();
-                        let left_node = tree.pop().unwrap();
-                        let size = left_node.len() + right_node.len();
-                        // let's have a fake priority, we'll set it later
-                        let merged = Node::Inner(0, size, [left_node, right_node]);
-                        tree.push(Box::new(merged));
-                    } else {
-                        break;
-                    }
-                }
-                (tree, leaves + 1)
-            },
+    /// Removes the element at position `index` and returns it,
+    /// replacing it by the last element.
+    /// This does not preserve ordering but avoids shifting elements.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// assert_eq!(t.swap_remove(1), 1);
+    /// assert!(t.iter().eq(&[0, 4, 2, 3]));
+    /// ```
+    pub fn swap_remove(&mut self, index: usize) -> C {
+        let len = self.len();
+        assert!(
+            index < len,
+            "swap_remove index (is {}) should be < len (is {})",
+            index,
+            len
         );
-        let right_node = tree.pop();
-        if let Some(mut right_node) = right_node {
-            // build the treap
-            while let Some(left_node) = tree.pop() {
-                let size = left_node.len() + right_node.len();
-                right_node = Box::new(Node::Inner(0, size, [left_node, right_node]));
-            }
-            let mut treap = ITreap { root: *right_node };
-            // now, fix priorities
-            let mut priorities: Vec<Priority> =
-                std::iter::repeat_with(random).take(leaves - 1).collect();
-            priorities.sort_unstable();
-            for_each_node_breadth_first(&mut treap.root, |node| match node {
-                Node::Inner(priority, _, _) => *priority = priorities.pop().unwrap(),
-                _ => (),
-            });
-
-            debug_assert!(treap.is_valid());
-            treap
+        let last = self.root.remove(len - 1);
+        if index == len - 1 {
+            last
         } else {
-            Default::default()
+            self.root.replace(index, last)
         }
     }
+    /// Removes the element at position `index` and returns it,
+    /// replacing it by the first element.
+    /// This is the mirror of `swap_remove` : ordering is not preserved
+    /// and only the first block and the block of `index` are modified.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// assert_eq!(t.swap_remove_front(3), 3);
+    /// assert!(t.iter().eq(&[1, 2, 0, 4]));
+    /// ```
+    pub fn swap_remove_front(&mut self, index: usize) -> C {
+        let len = self.len();
+        assert!(
+            index < len,
+            "swap_remove_front index (is {}) should be < len (is {})",
+            index,
+            len
+        );
+        let first = self.root.remove(0);
+        if index == 0 {
+            first
+        } else {
+            self.root.replace(index - 1, first)
+        }
+    }
+    /// Splits the indexed treap in two at given index.
+    /// `self` keeps elements `0..index` and the returned treap contains `index..len`.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// let end = t.split_off(6);
+    /// assert!(t.iter().eq(&[0, 1, 2, 3, 4, 5]));
+    /// assert!(end.iter().eq(&[6, 7, 8, 9]));
+    /// ```
+    pub fn split_off(&mut self, index: usize) -> Self {
+        assert!(index <= self.len(), "split index out of bounds");
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        let (left, right) = root.split(index);
+        self.root = left;
+        ITreap {
+            root: right,
+            rng: SmallRng::seed_from_u64(self.rng.gen()),
+            spare_blocks: Vec::new(),
+        }
+    }
+    /// Replaces all elements in given range by given new elements.
+    /// Removed elements are dropped.
+    /// Cost is O(log(n/B)+B+k) where k is the number of new elements
+    /// (plus dropping removed elements).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or goes past the end.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// t.splice(1..3, vec![7, 8, 9]);
+    /// assert!(t.iter().eq(&[0, 7, 8, 9, 3, 4]));
+    /// ```
+    pub fn splice<I: IntoIterator<Item = C>>(&mut self, range: Range<usize>, replace_with: I) {
+        assert!(range.start <= range.end, "splice range is decreasing");
+        let mut tail = self.split_off(range.end);
+        self.truncate(range.start);
+        self.extend(replace_with);
+        self.append(&mut tail);
+    }
+    /// Rotates the sequence in place so that the element at `mid` becomes the first,
+    /// like `[T]::rotate_left`.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid` is greater than the length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..6).collect();
+    /// t.rotate_left(2);
+    /// assert!(t.iter().eq(&[2, 3, 4, 5, 0, 1]));
+    /// ```
+    pub fn rotate_left(&mut self, mid: usize) {
+        assert!(mid <= self.len(), "rotation point out of bounds");
+        let mut tail = self.split_off(mid);
+        core::mem::swap(self, &mut tail);
+        self.append(&mut tail);
+    }
+    /// Rotates the sequence in place so that the last `k` elements come first,
+    /// like `[T]::rotate_right`.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is greater than the length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..6).collect();
+    /// t.rotate_right(2);
+    /// assert!(t.iter().eq(&[4, 5, 0, 1, 2, 3]));
+    /// ```
+    pub fn rotate_right(&mut self, k: usize) {
+        assert!(k <= self.len(), "rotation point out of bounds");
+        self.rotate_left(self.len() - k)
+    }
+    /// Keeps only the first `len` elements, dropping the others.
+    /// Does nothing if the treap is not longer than `len`.
+    /// Cost is O(log(n/B)+B) (plus dropping elements).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// t.truncate(3);
+    /// assert!(t.iter().eq(&[0, 1, 2]));
+    /// ```
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.split_off(len);
+        }
+    }
+    /// Resizes the treap to `new_len` elements, truncating it
+    /// or padding it with clones of `value`.
+    /// Cost is O(log(n/B)+B+k) where k is the number of new elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// t.resize(5, 7);
+    /// assert!(t.iter().eq(&[0, 1, 2, 7, 7]));
+    /// t.resize(1, 7);
+    /// assert!(t.iter().eq(&[0]));
+    /// ```
+    pub fn resize(&mut self, new_len: usize, value: C)
+    where
+        C: Clone,
+    {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len)
+        } else {
+            self.extend(core::iter::repeat_n(value, new_len - len))
+        }
+    }
+    /// Splits the (partitioned) treap at the first element failing given predicate.
+    /// `self` keeps all elements satisfying it and the returned treap
+    /// contains the others.
+    /// Cost is O(log(n/B)^2 + B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// let big = t.split_at_predicate(|&e| e < 7);
+    /// assert!(t.iter().copied().eq(0..7));
+    /// assert!(big.iter().copied().eq(7..10));
+    /// ```
+    pub fn split_at_predicate<P: FnMut(&C) -> bool>(&mut self, pred: P) -> Self {
+        let index = self.partition_point(pred);
+        self.split_off(index)
+    }
+    /// Moves all elements of `other` to the back of `self`, leaving `other` empty.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// let mut other: ITreap<_> = (3..6).collect();
+    /// t.append(&mut other);
+    /// assert!(t.iter().eq(&[0, 1, 2, 3, 4, 5]));
+    /// assert!(other.is_empty());
+    /// ```
+    pub fn append(&mut self, other: &mut Self) {
+        let left = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        let right = core::mem::replace(&mut other.root, Node::Leaf(Vec::new()));
+        self.root = Node::join(left, right, &mut self.rng);
+    }
+    /// Appends `other` like `append` but only if the resulting length
+    /// does not exceed `max_len`. Both treaps are left untouched on failure.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// let mut other: ITreap<_> = (3..6).collect();
+    /// assert!(t.try_append(&mut other, 5).is_err());
+    /// assert_eq!(other.len(), 3);
+    /// assert!(t.try_append(&mut other, 6).is_ok());
+    /// assert_eq!(t.len(), 6);
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn try_append(&mut self, other: &mut Self, max_len: usize) -> Result<(), ()> {
+        if self.len() + other.len() > max_len {
+            return Err(());
+        }
+        self.append(other);
+        Ok(())
+    }
+    /// Saves the current state of the treap so that it can later be
+    /// brought back with `restore`.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// let snapshot = t.snapshot();
+    /// t.push(5);
+    /// t.restore(snapshot);
+    /// assert!(t.iter().copied().eq(0..5));
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<C, B, M>
+    where
+        C: Clone,
+    {
+        Snapshot {
+            treap: self.clone(),
+        }
+    }
+    /// Reverts the treap to a previously saved state, dropping current content.
+    /// Cost is O(1) (plus dropping the current content).
+    pub fn restore(&mut self, snapshot: Snapshot<C, B, M>) {
+        *self = snapshot.treap;
+    }
+    /// Clones and adds all elements of given slice to the back.
+    /// Elements are directly spread into new full blocks which are then appended.
+    /// This is the densest layout (fewest leaves) but the first insertion
+    /// in each of these blocks splits it : use `extend_from_slice` to get
+    /// half full blocks instead, like `collect` does.
+    /// Cost is O(log(n/B)+B+k) where k is the slice length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// t.push_slice(&[3, 4, 5]);
+    /// assert!(t.iter().eq(&[0, 1, 2, 3, 4, 5]));
+    /// ```
+    pub fn push_slice(&mut self, src: &[C])
+    where
+        C: Clone,
+    {
+        self.append_blocks(src.chunks(B).map(|chunk| chunk.to_vec()))
+    }
+    /// Clones and adds all elements of `other` to the back, leaving it untouched.
+    /// Blocks of `other` are cloned as they are and gathered in a new tree
+    /// which is then appended.
+    /// Cost is O(log(n/B)+B+m) where m is the length of `other`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// let other: ITreap<_> = (3..6).collect();
+    /// t.extend_from_treap(&other);
+    /// assert!(t.iter().eq(&[0, 1, 2, 3, 4, 5]));
+    /// assert_eq!(other.len(), 3);
+    /// ```
+    pub fn extend_from_treap(&mut self, other: &Self)
+    where
+        C: Clone,
+    {
+        self.append_blocks(
+            oriented_blocks(&other.root, false)
+                .filter(|(block, _)| !block.is_empty())
+                .map(|(block, reversed)| oriented_iter(block, reversed).cloned().collect()),
+        )
+    }
+    /// Concatenates all given treaps, in order.
+    /// Cost is O(k(log(n/B)+B)) where k is the number of treaps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let parts = (0..3).map(|i| (2 * i..2 * i + 2).collect::<ITreap<_>>());
+    /// assert!(ITreap::concat(parts).iter().copied().eq(0..6));
+    /// ```
+    pub fn concat<I: IntoIterator<Item = Self>>(parts: I) -> Self {
+        parts
+            .into_iter()
+            .fold(ITreap::default(), |whole, part| whole + part)
+    }
+    /// Clones and adds all elements of the slice to the back.
+    /// The slice is cut into half blocks gathered in a balanced tree which is then appended.
+    /// Cost is O(log(n/B)+B+k) where k is the length of the slice.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..3).collect();
+    /// t.extend_from_slice(&[3, 4]);
+    /// assert!(t.iter().copied().eq(0..5));
+    /// ```
+    pub fn extend_from_slice(&mut self, items: &[C])
+    where
+        C: Clone,
+    {
+        self.append_blocks(items.chunks(B / 2).map(|chunk| chunk.to_vec()))
+    }
+    /// Gathers given (non-empty) blocks in a balanced tree and appends it.
+    /// Cost is O(log(n/B)+B+k) where k is the number of new blocks.
+    fn append_blocks<I: Iterator<Item = Vec<C>>>(&mut self, blocks: I) {
+        let new_root = build_from_blocks(blocks, &mut self.rng);
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        self.root = Node::join(root, new_root, &mut self.rng);
+    }
+    /// Merges several sorted treaps into a single sorted one.
+    /// On equal values, elements of earlier treaps come first.
+    /// Cost is O(n log(k)) where k is the number of treaps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let treaps: Vec<ITreap<_>> = (0..3).map(|s| (s..9).step_by(3).collect()).collect();
+    /// let merged = ITreap::merge_k_sorted(treaps);
+    /// assert!(merged.iter().copied().eq(0..9));
+    /// ```
+    pub fn merge_k_sorted(treaps: Vec<Self>) -> Self
+    where
+        C: Ord,
+    {
+        use core::cmp::Reverse;
+        let mut sources = treaps
+            .into_iter()
+            .map(|treap| treap.into_blocks().flatten())
+            .collect::<Vec<_>>();
+        let mut heads = sources
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(source, elements)| elements.next().map(|e| Reverse((e, source))))
+            .collect::<alloc::collections::BinaryHeap<_>>();
+        core::iter::from_fn(|| {
+            let Reverse((element, source)) = heads.pop()?;
+            if let Some(next) = sources[source].next() {
+                heads.push(Reverse((next, source)));
+            }
+            Some(element)
+        })
+        .collect()
+    }
+    /// Merges given sorted elements into the (sorted) indexed treap.
+    /// The tree is rebuilt after a two way merge, which is far
+    /// faster than inserting elements one by one for large batches.
+    /// On equal values, elements already in the treap come first.
+    /// Cost is O(n+m) where m is the number of new elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).map(|e| e * 2).collect();
+    /// t.merge_insert_sorted(vec![1, 2, 7]);
+    /// assert!(t.iter().eq(&[0, 1, 2, 2, 4, 6, 7, 8]));
+    /// ```
+    pub fn merge_insert_sorted<I: IntoIterator<Item = C>>(&mut self, sorted: I)
+    where
+        C: Ord,
+    {
+        let elements = self
+            .take_blocks()
+            .flatten()
+            .merge(sorted)
+            .collect::<Vec<_>>();
+        self.rebuild(elements);
+    }
+    /// Rebuilds the same sequence of elements with a different block size.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_, 8> = (0..100).collect();
+    /// let t: ITreap<_, 64> = t.rechunk();
+    /// assert!(t.iter().copied().eq(0..100));
+    /// ```
+    pub fn rechunk<const B2: usize>(self) -> ITreap<C, B2, M> {
+        self.into_blocks().flatten().collect()
+    }
+    /// Randomly permutes all elements using given random number generator.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// t.shuffle(&mut rand::thread_rng());
+    /// assert_eq!(t.len(), 10);
+    /// ```
+    pub fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut elements = self.take_blocks().flatten().collect::<Vec<_>>();
+        elements.shuffle(rng);
+        self.rebuild(elements);
+    }
+    /// Keeps only elements satisfying given predicate, preserving order.
+    /// Small adjacent blocks left after filtering are fused.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// t.retain(|e| e % 3 == 0);
+    /// assert!(t.iter().eq(&[0, 3, 6, 9]));
+    /// ```
+    pub fn retain<F: FnMut(&C) -> bool>(&mut self, mut f: F) {
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        self.root = root.retain(&mut f, &mut self.rng);
+    }
+    /// Stably reorders elements so that all the ones satisfying given predicate
+    /// come first. Returns the index of the first element not satisfying it.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..6).collect();
+    /// assert_eq!(t.partition_in_place(|e| e % 2 == 0), 3);
+    /// assert!(t.iter().eq(&[0, 2, 4, 1, 3, 5]));
+    /// ```
+    pub fn partition_in_place<P: FnMut(&C) -> bool>(&mut self, mut pred: P) -> usize {
+        let (mut matching, others): (Vec<_>, Vec<_>) =
+            self.take_blocks().flatten().partition(|e| pred(e));
+        let split = matching.len();
+        matching.extend(others);
+        self.rebuild(matching);
+        split
+    }
+    /// Consumes the treap, splitting its elements in two balanced treaps:
+    /// the ones satisfying given predicate and the others.
+    /// Relative order is preserved in both.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..6).collect();
+    /// let (even, odd) = t.partition(|e| e % 2 == 0);
+    /// assert!(even.iter().eq(&[0, 2, 4]));
+    /// assert!(odd.iter().eq(&[1, 3, 5]));
+    /// ```
+    pub fn partition<P: FnMut(&C) -> bool>(self, mut pred: P) -> (Self, Self) {
+        let (matching, others): (Vec<_>, Vec<_>) =
+            self.into_blocks().flatten().partition(|e| pred(e));
+        (matching.into_iter().collect(), others.into_iter().collect())
+    }
+    /// Replaces all elements by the ones in given vector.
+    /// When sizes are close enough the old leaf blocks and node boxes are recycled
+    /// to limit allocations, otherwise we just rebuild from scratch.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// t.replace_all_from_vec(vec![3, 2, 1]);
+    /// assert!(t.iter().eq(&[3, 2, 1]));
+    /// ```
+    pub fn replace_all_from_vec(&mut self, v: Vec<C>) {
+        let old_len = self.len();
+        if v.len() / 2 > old_len || old_len / 2 > v.len() {
+            self.rebuild(v);
+            return;
+        }
+        let (mut spare_blocks, mut spare_boxes) = self.take_allocations();
+        let mut elements = v.into_iter().peekable();
+        let blocks = core::iter::from_fn(|| {
+            elements.peek()?;
+            let mut block = spare_blocks.pop().unwrap_or_default();
+            block.clear();
+            block.extend(elements.by_ref().take(B / 2));
+            Some(block)
+        });
+        self.root = build_reusing_boxes(blocks, &mut self.rng, &mut spare_boxes);
+    }
+    /// Returns the index of the partition point according to given predicate
+    /// (the index of the first element for which the predicate is false).
+    /// The treap is assumed to be partitioned, as for slices.
+    /// Cost is O(log(n/B)^2 + log(B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert_eq!(t.partition_point(|&e| e < 4), 4);
+    /// ```
+    pub fn partition_point<P: FnMut(&C) -> bool>(&self, pred: P) -> usize {
+        self.root.partition_point(pred)
+    }
+    /// Binary searches given element in the (sorted) treap.
+    /// Returns `Ok` with the index of the first matching element if found
+    /// and `Err` with the index where it could be inserted if not found.
+    /// Cost is O(log(n/B)^2 + log(B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).map(|e| e * 2).collect();
+    /// assert_eq!(t.binary_search(&4), Ok(2));
+    /// assert_eq!(t.binary_search(&5), Err(3));
+    /// ```
+    pub fn binary_search(&self, x: &C) -> Result<usize, usize>
+    where
+        C: Ord,
+    {
+        let index = self.partition_point(|e| e < x);
+        if self.get(index) == Some(x) {
+            Ok(index)
+        } else {
+            Err(index)
+        }
+    }
+    /// Inserts given element in the (sorted) treap, keeping it sorted,
+    /// and returns its index.
+    /// The element goes after all equal ones.
+    /// The result is only meaningful if the treap is already sorted.
+    /// Cost is O(log(n/B)^2 + B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = vec![1, 3, 5].into_iter().collect();
+    /// assert_eq!(t.insert_sorted(4), 2);
+    /// assert!(t.iter().eq(&[1, 3, 4, 5]));
+    /// ```
+    pub fn insert_sorted(&mut self, x: C) -> usize
+    where
+        C: Ord,
+    {
+        let index = self.partition_point(|e| *e <= x);
+        self.insert(index, x);
+        index
+    }
+    /// Returns the element of the (sorted) treap closest to `target`,
+    /// together with its index, or `None` if the treap is empty.
+    /// An element equal to `target` is the closest one. Otherwise only an order
+    /// is available, so both neighbours of `target` tie and the lower one wins.
+    /// Cost is O(log(n/B)^2 + log(B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![1, 5, 9].into_iter().collect();
+    /// assert_eq!(t.nearest(&0), Some((0, &1)));
+    /// assert_eq!(t.nearest(&5), Some((1, &5)));
+    /// assert_eq!(t.nearest(&8), Some((1, &5)));
+    /// assert_eq!(t.nearest(&10), Some((2, &9)));
+    /// ```
+    pub fn nearest(&self, target: &C) -> Option<(usize, &C)>
+    where
+        C: Ord,
+    {
+        let index = self.partition_point(|e| e < target);
+        let above = self.root.get(index).map(|e| (index, e));
+        let below = index
+            .checked_sub(1)
+            .and_then(|i| self.root.get(i).map(|e| (i, e)));
+        match above {
+            Some((_, e)) if e == target => above,
+            _ => below.or(above),
+        }
+    }
+    /// Binary searches all given (sorted) keys in the (sorted) treap,
+    /// with the same results as `binary_search`.
+    /// Keys are merged against the blocks in a single pass instead
+    /// of descending the tree once per key.
+    /// Cost is O(n/B + k log(B)) where k is the number of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).map(|e| e * 2).collect();
+    /// assert_eq!(t.batch_search(&[4, 5, 30]), vec![Ok(2), Err(3), Err(10)]);
+    /// ```
+    pub fn batch_search<'a, I: IntoIterator<Item = &'a C>>(
+        &self,
+        keys: I,
+    ) -> Vec<Result<usize, usize>>
+    where
+        C: Ord + 'a,
+    {
+        let mut keys = keys.into_iter().peekable();
+        let mut results = Vec::new();
+        let mut chunk_start = 0;
+        for (chunk, reversed) in oriented_blocks(&self.root, false) {
+            if keys.peek().is_none() {
+                break;
+            }
+            let at = |index: usize| {
+                if reversed {
+                    &chunk[chunk.len() - 1 - index]
+                } else {
+                    &chunk[index]
+                }
+            };
+            if let Some(last) = chunk.len().checked_sub(1).map(at) {
+                while let Some(key) = keys.next_if(|&key| key <= last) {
+                    let index = if reversed {
+                        chunk.len() - chunk.partition_point(|e| e >= key)
+                    } else {
+                        chunk.partition_point(|e| e < key)
+                    };
+                    results.push(if at(index) == key {
+                        Ok(chunk_start + index)
+                    } else {
+                        Err(chunk_start + index)
+                    });
+                }
+            }
+            chunk_start += chunk.len();
+        }
+        let len = self.len();
+        results.extend(keys.map(|_| Err(len)));
+        results
+    }
+    /// Returns whether the (sorted) treap contains at least one element in `[lo, hi]`.
+    /// Cost is O(log(n/B)^2 + log(B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).map(|e| e * 10).collect();
+    /// assert!(t.covers(&15, &20));
+    /// assert!(!t.covers(&11, &19));
+    /// ```
+    pub fn covers(&self, lo: &C, hi: &C) -> bool
+    where
+        C: Ord,
+    {
+        let start = self.partition_point(|e| e < lo);
+        self.between(start..self.len())
+            .next()
+            .map(|e| e <= hi)
+            .unwrap_or(false)
+    }
+    /// Counts elements strictly smaller than `x`.
+    /// The treap does not need to be sorted but since no order statistics
+    /// are maintained we have to scan everything.
+    /// For sorted treaps use `partition_point` instead.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![5, 1, 4, 2, 3].into_iter().collect();
+    /// assert_eq!(t.count_less_than(&3), 2);
+    /// ```
+    pub fn count_less_than(&self, x: &C) -> usize
+    where
+        C: Ord,
+    {
+        self.stored_blocks()
+            .map(|block| block.iter().filter(|e| *e < x).count())
+            .sum()
+    }
+    /// Counts how many times each value appears.
+    /// Only available with the `std` feature.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec!['a', 'b', 'a'].into_iter().collect();
+    /// let frequencies = t.frequencies();
+    /// assert_eq!(frequencies[&'a'], 2);
+    /// assert_eq!(frequencies[&'b'], 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn frequencies(&self) -> std::collections::HashMap<C, usize>
+    where
+        C: Eq + core::hash::Hash + Clone,
+    {
+        let mut frequencies = std::collections::HashMap::new();
+        for block in self.stored_blocks() {
+            for element in block {
+                *frequencies.entry(element.clone()).or_insert(0) += 1;
+            }
+        }
+        frequencies
+    }
+    /// Returns the indices range of the longest non-decreasing run of elements
+    /// (the first one if several have the same length).
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![3, 1, 2, 2, 5, 0, 4].into_iter().collect();
+    /// assert_eq!(t.longest_sorted_run(), 1..5);
+    /// ```
+    pub fn longest_sorted_run(&self) -> Range<usize>
+    where
+        C: PartialOrd,
+    {
+        let mut longest = 0..0;
+        let mut run_start = 0;
+        let mut previous = None;
+        for (index, element) in self.iter().enumerate() {
+            if previous.map(|p| p > element).unwrap_or(false) {
+                run_start = index;
+            }
+            if index + 1 - run_start > longest.len() {
+                longest = run_start..index + 1;
+            }
+            previous = Some(element);
+        }
+        longest
+    }
+    /// Returns the indices of all elements differing from their predecessor.
+    /// Index 0 is always included for a non-empty treap.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec!['a', 'a', 'b', 'a', 'a'].into_iter().collect();
+    /// assert_eq!(t.change_points(), vec![0, 2, 3]);
+    /// ```
+    pub fn change_points(&self) -> Vec<usize>
+    where
+        C: PartialEq,
+    {
+        let mut previous = None;
+        self.iter()
+            .enumerate()
+            .filter_map(|(index, element)| {
+                let changed = previous != Some(element);
+                previous = Some(element);
+                if changed {
+                    Some(index)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    /// Returns the element whose key is the median of all keys
+    /// (the lower median for even lengths), or `None` if the treap is empty.
+    /// Elements are not reordered: keys are computed and selected on the side.
+    /// Cost is O(n) on average.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![("a", 30), ("b", 10), ("c", 20)].into_iter().collect();
+    /// assert_eq!(t.median_by_key(|r| r.1), Some(&("c", 20)));
+    /// ```
+    pub fn median_by_key<K: Ord, F: FnMut(&C) -> K>(&self, mut f: F) -> Option<&C> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut keys = self
+            .iter()
+            .enumerate()
+            .map(|(index, element)| (f(element), index))
+            .collect::<Vec<_>>();
+        let middle = (keys.len() - 1) / 2;
+        let (_, &mut (_, index), _) = keys.select_nth_unstable(middle);
+        self.root.get(index)
+    }
+    /// Consumes the indexed treap, returning all its elements in a vector.
+    /// The first block is extended with all others, reusing its allocation.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..4).collect();
+    /// assert_eq!(t.into_vec(), vec![0, 1, 2, 3]);
+    /// ```
+    pub fn into_vec(self) -> Vec<C> {
+        let len = self.len();
+        let mut blocks = self.into_blocks();
+        let mut elements = blocks.next().unwrap_or_default();
+        elements.reserve_exact(len - elements.len());
+        blocks.for_each(|block| elements.extend(block));
+        elements
+    }
+    /// Clones all elements into a vector.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..4).collect();
+    /// assert_eq!(t.to_vec(), vec![0, 1, 2, 3]);
+    /// ```
+    pub fn to_vec(&self) -> Vec<C>
+    where
+        C: Clone,
+    {
+        let mut elements = Vec::with_capacity(self.len());
+        elements.extend(self.iter().cloned());
+        elements
+    }
+    /// Consumes the indexed treap, returning all its elements sorted.
+    /// Cost is O(n log(n)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![3, 1, 2].into_iter().collect();
+    /// assert_eq!(t.into_sorted_vec(), vec![1, 2, 3]);
+    /// ```
+    pub fn into_sorted_vec(self) -> Vec<C>
+    where
+        C: Ord,
+    {
+        let mut elements = self.into_vec();
+        elements.sort();
+        elements
+    }
+    /// Removes all elements in given range and returns them as a new indexed treap.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// let middle = t.cut(2..5);
+    /// assert!(t.iter().eq(&[0, 1, 5, 6, 7, 8, 9]));
+    /// assert!(middle.iter().eq(&[2, 3, 4]));
+    /// ```
+    pub fn cut(&mut self, range: Range<usize>) -> Self {
+        let mut end = self.split_off(range.end);
+        let middle = self.split_off(range.start);
+        self.append(&mut end);
+        middle
+    }
+    /// Fuses all pairs of sibling leaves whose combined size fits in a block,
+    /// improving blocks occupancy without rebuilding the whole tree.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10_000).collect();
+    /// t.split_off(5_000);
+    /// t.coalesce();
+    /// assert!(t.iter().copied().eq(0..5_000));
+    /// ```
+    pub fn coalesce(&mut self) {
+        self.root.coalesce()
+    }
+    /// Applies all pending reversals (see `reverse`) so that all blocks
+    /// store their elements in sequence order.
+    /// Cost is O(n).
+    pub fn normalize(&mut self) {
+        self.root.normalize()
+    }
+    /// Returns the summary of all elements in given range of indices.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or goes past the end.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::{Sum, SumTreap};
+    ///
+    /// let t: SumTreap<_> = (0..10).collect();
+    /// assert_eq!(t.range_fold(2..5), Sum(9));
+    /// ```
+    pub fn range_fold(&self, range: Range<usize>) -> M {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "fold range out of bounds"
+        );
+        self.root.range_summary(range)
+    }
+    /// Returns the number of elements in the indexed treap.
+    /// Cost is O(1).
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+    /// Returns `true` if the indexed treap contains no elements.
+    /// Cost is O(1).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the number of elements the treap can hold in its current blocks
+    /// without reallocating, spare blocks planned by `with_capacity` and `reserve` included.
+    /// Cost is O(n/B).
+    pub fn capacity(&self) -> usize {
+        self.root.capacity() + self.spare_capacity()
+    }
+    /// Returns the capacity of all spare blocks.
+    fn spare_capacity(&self) -> usize {
+        self.spare_blocks.iter().map(|block| block.capacity()).sum()
+    }
+    /// Makes room for pushing `additional` elements without reallocating.
+    /// The last block grows towards a full block and spare blocks are planned
+    /// for the rest, see `with_capacity`.
+    /// Cost is O(n/B) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<u32> = (0..10).collect();
+    /// t.reserve(100);
+    /// assert!(t.capacity() >= 110);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        let remaining = self.root.reserve_back(additional);
+        self.plan_blocks(remaining);
+    }
+    /// Shrinks the capacities of all blocks as much as possible
+    /// and drops spare blocks,
+    /// typically after a `rebalance` or once the treap stops growing.
+    /// Cost is O(n/B) plus the reallocations.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<u32> = (0..10).collect();
+    /// t.reserve(100);
+    /// t.shrink_to_fit();
+    /// assert!(t.capacity() >= 10 && t.capacity() < 110);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.spare_blocks = Vec::new();
+        self.root.shrink_to_fit()
+    }
+    /// Rebuilds the tree from scratch into a perfectly balanced one,
+    /// with the same layout as `collect` : blocks are half full (but the last one),
+    /// leaving room for upcoming insertions.
+    /// After many random insertions and removals the tree is only balanced
+    /// in expectation and blocks may be nearly empty : this is a maintenance
+    /// operation worth calling between phases of a computation.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = ITreap::new();
+    /// (0..10_000).for_each(|e| t.insert(e / 2, e));
+    /// let elements: Vec<_> = t.iter().copied().collect();
+    /// t.rebalance();
+    /// assert!(t.iter().eq(&elements));
+    /// let collected: ITreap<_> = elements.into_iter().collect();
+    /// assert_eq!(t.leaf_count(), collected.leaf_count());
+    /// ```
+    pub fn rebalance(&mut self) {
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        self.rebuild(ITreap::<C, B, M>::from_root(root));
+        debug_assert!(self.is_valid());
+    }
+    /// Loops on all leaf blocks, in order, as slices in sequence order.
+    /// `reverse` is lazy : a block below a pending reversal is stored backwards
+    /// and can only be seen as a slice in sequence order once reordered.
+    /// Pending reversals are therefore applied on the way down, hence the mutable borrow.
+    /// See `oriented_chunks` for read-only access.
+    /// Cost is O(n/B) (plus O(B) per block below a pending reversal).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10_000).collect();
+    /// t.reverse_range(100..5_000);
+    /// let elements: Vec<_> = t.iter().copied().collect();
+    /// assert!(t.chunks().flatten().eq(&elements));
+    /// ```
+    pub fn chunks(&mut self) -> impl Iterator<Item = &[C]> + '_ {
+        self.leaves_mut().map(|block| block.as_slice())
+    }
+    /// Loops on all leaf blocks, in order, without modifying the treap.
+    /// Each block comes as it is stored, together with a flag telling
+    /// whether its elements should be read backwards.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10_000).collect();
+    /// t.reverse();
+    /// let t = &t;
+    /// assert!(t.oriented_chunks().all(|(_, backwards)| backwards));
+    /// assert!(t
+    ///     .oriented_chunks()
+    ///     .flat_map(|(block, _)| block.iter().rev())
+    ///     .eq(t.iter()));
+    /// ```
+    pub fn oriented_chunks(&self) -> impl Iterator<Item = (&[C], bool)> + '_ {
+        oriented_blocks(&self.root, false)
+    }
+    /// Loops on all leaf blocks as they are stored, ignoring pending reversals.
+    /// Only for order insensitive processing or comparisons of identical layouts.
+    /// Cost is O(n/B).
+    fn stored_blocks(&self) -> impl Iterator<Item = &[C]> + '_ {
+        oriented_blocks(&self.root, false).map(|(block, _)| block)
+    }
+    /// Loops on all leaves, in order, applying pending reversals on the way down.
+    /// Cost is O(n/B) (plus O(B) per block below a pending reversal).
+    fn leaves_mut(&mut self) -> impl Iterator<Item = &mut Vec<C>> + '_ {
+        let mut remaining_nodes = vec![&mut self.root];
+        core::iter::from_fn(move || {
+            while let Some(node) = remaining_nodes.pop() {
+                node.push_down();
+                match node {
+                    Node::Inner(_, _, _, _, [left, right]) => {
+                        remaining_nodes.push(right);
+                        remaining_nodes.push(left);
+                    }
+                    Node::Leaf(block) => return Some(block),
+                }
+            }
+            None
+        })
+    }
+    /// Loops on all leaf blocks, in order, as iterators on their elements.
+    /// Cost is O(n/B).
+    fn block_iters<'a>(&'a self) -> impl Iterator<Item = OrientedBlock<'a, C>> + 'a {
+        oriented_blocks(&self.root, false).map(|(block, reversed)| oriented_iter(block, reversed))
+    }
+    /// Returns the leaf block of given index, as a slice in sequence order,
+    /// or `None` if there are not that many blocks.
+    /// Like `chunks` this takes `&mut self` since pending reversals are applied on the way :
+    /// with a shared borrow use `oriented_chunks().nth(block_index)` instead.
+    /// Nodes only cache their number of elements, not their number of leaves,
+    /// so we cannot descend directly to the block and have to count leaves
+    /// from the left.
+    /// Cost is O(n/B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10).collect();
+    /// t.reverse();
+    /// assert!(t.block_at(0).unwrap().iter().copied().eq((0..10).rev()));
+    /// assert_eq!(t.block_at(1), None);
+    /// ```
+    pub fn block_at(&mut self, block_index: usize) -> Option<&[C]> {
+        self.chunks().nth(block_index)
+    }
+    /// Moves out all leaf blocks, in order.
+    /// Cost is O(n/B).
+    pub(super) fn into_blocks(mut self) -> impl Iterator<Item = Vec<C>> {
+        self.take_blocks()
+    }
+    /// Adds given block at the front, fused with the first leaf if they fit.
+    /// Cost is O(log(n/B)+B).
+    pub(super) fn push_block_front(&mut self, block: Vec<C>) {
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        self.root = Node::join(Node::Leaf(block), root, &mut self.rng);
+    }
+    /// Adds given block at the back, fused with the last leaf if they fit.
+    /// Cost is O(log(n/B)+B).
+    pub(super) fn push_block_back(&mut self, block: Vec<C>) {
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        self.root = Node::join(root, Node::Leaf(block), &mut self.rng);
+    }
+    /// Moves out the first leaf block.
+    /// Cost is O(log(n/B)).
+    pub(super) fn pop_block_front(&mut self) -> Vec<C> {
+        let rest = self.split_off(self.root.extreme_leaf_len(LEFT));
+        core::mem::replace(self, rest).into_blocks().concat()
+    }
+    /// Moves out the last leaf block.
+    /// Cost is O(log(n/B)).
+    pub(super) fn pop_block_back(&mut self) -> Vec<C> {
+        self.split_off(self.len() - self.root.extreme_leaf_len(RIGHT))
+            .into_blocks()
+            .concat()
+    }
+    /// Loops on all elements together with the index of the block they belong to.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert!(t.iter_with_block().all(|(block, _)| block == 0));
+    /// ```
+    pub fn iter_with_block<'a>(&'a self) -> impl Iterator<Item = (usize, &'a C)> + 'a {
+        self.block_iters()
+            .enumerate()
+            .flat_map(|(index, block)| block.map(move |e| (index, e)))
+    }
+    /// Builds a new indexed treap by mapping all elements in order
+    /// while threading a mutable state, like `Iterator::scan`.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![1, 3, 6, 10].into_iter().collect();
+    /// let sums = t.scan_map(0, |sum, e| {
+    ///     *sum += e;
+    ///     *sum
+    /// });
+    /// assert!(sums.iter().eq(&[1, 4, 10, 20]));
+    /// ```
+    pub fn scan_map<D, S, F: FnMut(&mut S, &C) -> D>(&self, init: S, mut f: F) -> ITreap<D, B> {
+        let mut state = init;
+        self.iter().map(|e| f(&mut state, e)).collect()
+    }
+    /// Applies given function to each window of `window` consecutive elements
+    /// and collects the results, yielding `len - window + 1` of them
+    /// (or none if the treap is too small).
+    /// Cost is O(n*window).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (1..6).collect();
+    /// let sums = t.rolling(3, |w| w.iter().copied().sum::<u32>());
+    /// assert!(sums.iter().eq(&[6, 9, 12]));
+    /// ```
+    pub fn rolling<D, F: FnMut(&[&C]) -> D>(&self, window: usize, mut f: F) -> ITreap<D, B> {
+        assert!(window > 0, "window size must be non-zero");
+        let mut current = alloc::collections::VecDeque::with_capacity(window);
+        self.iter()
+            .filter_map(|e| {
+                if current.len() == window {
+                    current.pop_front();
+                }
+                current.push_back(e);
+                if current.len() == window {
+                    Some(f(current.make_contiguous()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    /// Consumes the treap, converting all elements with a fallible function.
+    /// Stops at the first error and returns it.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec!["1", "2", "3"].into_iter().collect();
+    /// let numbers = t.try_map(|s| s.parse::<u32>()).unwrap();
+    /// assert!(numbers.iter().eq(&[1, 2, 3]));
+    /// ```
+    pub fn try_map<D, E, F: FnMut(C) -> Result<D, E>>(self, f: F) -> Result<ITreap<D, B>, E> {
+        self.into_blocks().flatten().map(f).collect()
+    }
+    /// Loops on all elements.
+    /// Cost is O(n).
+    pub fn iter(&self) -> Iter<'_, C, B, M> {
+        self.between(0..self.root.len())
+    }
+    /// Returns `true` if the treap contains an element equal to `x`.
+    /// Cost is O(n) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert!(t.contains(&3));
+    /// assert!(!t.contains(&10));
+    /// ```
+    pub fn contains(&self, x: &C) -> bool
+    where
+        C: PartialEq,
+    {
+        self.iter().any(|e| e == x)
+    }
+    /// Returns the index of the first element satisfying given predicate.
+    /// Cost is O(n) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert_eq!(t.position(|&e| e * e > 10), Some(4));
+    /// ```
+    pub fn position<P: FnMut(&C) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.iter().position(predicate)
+    }
+    /// Returns the first element satisfying given predicate.
+    /// Cost is O(n) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert_eq!(t.find(|&&e| e > 6), Some(&7));
+    /// ```
+    pub fn find<P: FnMut(&&C) -> bool>(&self, predicate: P) -> Option<&C> {
+        self.iter().find(predicate)
+    }
+    /// Returns the first non-`None` result of given function applied to
+    /// all (index, element) pairs in order, together with its index.
+    /// Cost is O(n) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![1, 3, 4, 6].into_iter().collect();
+    /// let found = t.find_map_indexed(|i, &e| if e % 2 == 0 { Some(e * i) } else { None });
+    /// assert_eq!(found, Some((2, 8)));
+    /// ```
+    pub fn find_map_indexed<D, F: FnMut(usize, &C) -> Option<D>>(
+        &self,
+        mut f: F,
+    ) -> Option<(usize, D)> {
+        let mut offset = 0;
+        for block in self.block_iters() {
+            let block_len = block.len();
+            let found = block
+                .enumerate()
+                .find_map(|(i, e)| f(offset + i, e).map(|d| (offset + i, d)));
+            if found.is_some() {
+                return found;
+            }
+            offset += block_len;
+        }
+        None
+    }
+    /// Applies a fallible function to all elements in order,
+    /// stopping at the first error and returning it.
+    /// Cost is O(n) in the worst case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// let mut sum = 0;
+    /// let r = t.try_for_each(|&e| if e < 4 { sum += e; Ok(()) } else { Err(e) });
+    /// assert_eq!(r, Err(4));
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn try_for_each<E, F: FnMut(&C) -> Result<(), E>>(&self, mut f: F) -> Result<(), E> {
+        self.block_iters()
+            .try_for_each(|mut block| block.try_for_each(&mut f))
+    }
+    /// Loops on the projections of all elements by given function.
+    /// We go block by block instead of through the generic range machinery
+    /// of `iter`.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = vec![(1, 'a'), (2, 'b')].into_iter().collect();
+    /// assert!(t.iter_map(|(_, c)| *c).eq(vec!['a', 'b']));
+    /// ```
+    pub fn iter_map<'a, D, F: FnMut(&'a C) -> D + 'a>(
+        &'a self,
+        f: F,
+    ) -> impl Iterator<Item = D> + 'a {
+        self.block_iters().flatten().map(f)
+    }
+    /// Loops on all elements starting at given index.
+    /// Cost is O(log(n/B) + k) where k designates the number of elements we should loop upon.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t: ITreap<_> = (0..10).collect();
+    /// assert!(t.iter_from(7).eq(&[7, 8, 9]));
+    /// ```
+    pub fn iter_from<'a>(&'a self, start: usize) -> impl Iterator<Item = &'a C> + 'a {
+        self.between(start..self.len())
+    }
+    /// Loop on all elements corresponding to indices in given range.
+    /// Cost is O(log(n/B) + k) where k designates the number of elements we should loop upon.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let t:ITreap<_> = (0..10).map(|e| e*2).collect();
+    /// assert!(t.between(1..4).eq(&[2, 4, 6]))
+    /// ```
+    pub fn between(&self, selection: core::ops::Range<usize>) -> Iter<'_, C, B, M> {
+        Iter::new(&self.root, selection)
+    }
+}
+
+/// Methods giving mutable access to elements are only available
+/// when no summary needs to be maintained.
+impl<C, const B: usize> ITreap<C, B> {
+    /// Mutably borrows the first element, or returns `None` if the treap is empty.
+    /// Cost is O(log(n/B)).
+    pub fn first_mut(&mut self) -> Option<&mut C> {
+        self.root.first_mut()
+    }
+    /// Mutably borrows the last element, or returns `None` if the treap is empty.
+    /// Cost is O(log(n/B)).
+    pub fn last_mut(&mut self) -> Option<&mut C> {
+        self.root.last_mut()
+    }
+    /// Mutably borrows the element at position `i`, or returns `None` if out of bounds.
+    /// Cost is O(log(n/B)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (1..4).collect();
+    /// *t.get_mut(0).unwrap() = 7;
+    /// assert!(t.get_mut(3).is_none());
+    /// assert!(t.iter().eq(&[7, 2, 3]));
+    /// ```
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut C> {
+        self.root.get_mut(i)
+    }
+    /// Mutably borrows the elements at all given positions at once,
+    /// like `[T]::get_disjoint_mut`.
+    /// Returns `None` if any index is out of bounds or if two indices are equal.
+    /// Cost is O(N log(N) + N log(n/B) + NB).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// let [a, b] = t.get_disjoint_mut([4, 1]).unwrap();
+    /// std::mem::swap(a, b);
+    /// assert!(t.iter().eq(&[0, 4, 2, 3, 1]));
+    /// assert!(t.get_disjoint_mut([2, 2]).is_none());
+    /// assert!(t.get_disjoint_mut([0, 5]).is_none());
+    /// ```
+    pub fn get_disjoint_mut<const N: usize>(&mut self, indices: [usize; N]) -> Option<[&mut C; N]> {
+        let mut requests: [(usize, usize); N] = core::array::from_fn(|slot| (indices[slot], slot));
+        requests.sort_unstable();
+        if requests.windows(2).any(|w| w[0].0 == w[1].0)
+            || requests
+                .last()
+                .is_some_and(|&(index, _)| index >= self.len())
+        {
+            return None;
+        }
+        let mut borrows: [Option<&mut C>; N] = core::array::from_fn(|_| None);
+        self.root.get_disjoint_mut(&mut requests, &mut borrows);
+        Some(borrows.map(|borrow| borrow.unwrap()))
+    }
+    /// Mutably borrows the front element, like `VecDeque::front_mut`.
+    /// This is an alias of `first_mut`.
+    pub fn peek_front_mut(&mut self) -> Option<&mut C> {
+        self.first_mut()
+    }
+    /// Mutably borrows the back element, like `VecDeque::back_mut`.
+    /// This is an alias of `last_mut`.
+    pub fn peek_back_mut(&mut self) -> Option<&mut C> {
+        self.last_mut()
+    }
+    /// Reverses the order of all elements.
+    /// The reversal is recorded in the root and only pushed down
+    /// the paths later operations go through.
+    /// Cost is O(1) (or O(B) if the treap holds a single block).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10_000).collect();
+    /// t.reverse();
+    /// assert_eq!(t[0], 9_999);
+    /// t.insert(0, 10_000);
+    /// assert!(t.iter().copied().eq((0..=10_000).rev()));
+    /// ```
+    pub fn reverse(&mut self) {
+        self.root.toggle_reversal()
+    }
+    /// Reverses the order of elements in given range.
+    /// The range is split out, lazily reversed and joined back.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or goes past the end of the treap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..6).collect();
+    /// t.reverse_range(1..4);
+    /// assert!(t.iter().eq(&[0, 3, 2, 1, 4, 5]));
+    /// ```
+    pub fn reverse_range(&mut self, range: Range<usize>) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "reversal range out of bounds"
+        );
+        if range.len() < 2 {
+            return;
+        }
+        let root = core::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+        let (left, right) = root.split(range.end);
+        let (left, mut middle) = left.split(range.start);
+        middle.toggle_reversal();
+        let left = Node::join(left, middle, &mut self.rng);
+        self.root = Node::join(left, right, &mut self.rng);
+    }
+    /// Loops on all leaf blocks, in order, as mutable slices in sequence order.
+    /// Cost is O(n/B) (plus O(B) per block below a pending reversal).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..10_000).collect();
+    /// t.chunks_mut().for_each(|chunk| chunk.reverse());
+    /// assert!(t.chunks().all(|chunk| chunk.windows(2).all(|w| w[0] > w[1])));
+    /// ```
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut [C]> + '_ {
+        self.leaves_mut().map(|block| block.as_mut_slice())
+    }
+    /// Mutably loops on all elements.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..4).collect();
+    /// t.iter_mut().for_each(|e| *e *= 2);
+    /// assert!(t.iter().eq(&[0, 2, 4, 6]));
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, C, B> {
+        let len = self.root.len();
+        self.between_mut(0..len)
+    }
+    /// Mutably loops on all elements corresponding to indices in given range.
+    /// Cost is O(log(n/B) + k) where k designates the number of elements we should loop upon.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// t.between_mut(1..3).for_each(|e| *e *= 10);
+    /// assert!(t.iter().eq(&[0, 10, 20, 3, 4]));
+    /// ```
+    pub fn between_mut(&mut self, selection: core::ops::Range<usize>) -> IterMut<'_, C, B> {
+        IterMut::new(&mut self.root, selection)
+    }
+    /// Overwrites elements at given indices with given values.
+    /// Updates are sorted by index and applied in a single in-order pass
+    /// (on duplicated indices the last update wins).
+    /// Updates with out of range indices are returned.
+    /// Cost is O(n/B + k log(k)) where k is the number of updates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..5).collect();
+    /// let rejected = t.scatter(vec![(3, 30), (10, 100), (1, 10)]);
+    /// assert!(t.iter().eq(&[0, 10, 2, 30, 4]));
+    /// assert_eq!(rejected, vec![(10, 100)]);
+    /// ```
+    pub fn scatter<I: IntoIterator<Item = (usize, C)>>(&mut self, updates: I) -> Vec<(usize, C)> {
+        let mut updates = updates.into_iter().collect::<Vec<_>>();
+        updates.sort_by_key(|(index, _)| *index);
+        let mut updates = updates.into_iter().peekable();
+        let mut chunk_start = 0;
+        for chunk in self.chunks_mut() {
+            if updates.peek().is_none() {
+                break;
+            }
+            let chunk_end = chunk_start + chunk.len();
+            while let Some((index, value)) = updates.next_if(|(index, _)| *index < chunk_end) {
+                chunk[index - chunk_start] = value;
+            }
+            chunk_start = chunk_end;
+        }
+        updates.collect()
+    }
+    /// Walks `self` and `other` in lockstep, applying `f` on each pair of elements
+    /// (up to the shortest length) to update `self` in place.
+    /// Cost is O(n).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::ITreap;
+    ///
+    /// let mut t: ITreap<_> = (0..4).collect();
+    /// let other: ITreap<_> = (10..13).collect();
+    /// t.zip_mut(&other, |e, o| *e += o);
+    /// assert!(t.iter().eq(&[10, 12, 14, 3]));
+    /// ```
+    pub fn zip_mut<D, const B2: usize, F: FnMut(&mut C, &D)>(
+        &mut self,
+        other: &ITreap<D, B2>,
+        mut f: F,
+    ) {
+        self.chunks_mut()
+            .flat_map(|chunk| chunk.iter_mut())
+            .zip(other.iter())
+            .for_each(|(e, o)| f(e, o))
+    }
+}
+
+impl<C, const B: usize, M> Drop for ITreap<C, B, M> {
+    /// Dismantles the tree without recursion, see `Node::dismantle`.
+    fn drop(&mut self) {
+        core::mem::replace(&mut self.root, Node::Leaf(Vec::new())).dismantle()
+    }
+}
+
+impl<C, const B: usize, M> core::default::Default for ITreap<C, B, M> {
+    fn default() -> Self {
+        ITreap::from_root(Node::Leaf(Vec::new()))
+    }
+}
+
+impl<C, const B: usize, M> ITreap<C, B, M> {
+    /// Wraps given root, with a randomly seeded generator.
+    pub(super) fn from_root(root: Node<C, B, M>) -> Self {
+        let () = Node::<C, B, M>::VALID_BLOCK_SIZE;
+        ITreap {
+            root,
+            rng: SmallRng::seed_from_u64(fresh_seed()),
+            spare_blocks: Vec::new(),
+        }
+    }
+    /// Moves out all leaf blocks, in order, leaving the treap empty
+    /// but keeping its generator.
+    /// Cost is O(n/B).
+    fn take_blocks(&mut self) -> impl Iterator<Item = Vec<C>> {
+        node_blocks(core::mem::replace(&mut self.root, Node::Leaf(Vec::new())))
+    }
+    /// Dismantles the tree, returning its leaf blocks (in no particular order)
+    /// and the emptied boxes of all its nodes below the root.
+    fn take_allocations(&mut self) -> (Vec<Vec<C>>, SpareBoxes<C, B, M>) {
+        let mut blocks = Vec::new();
+        let mut boxes = Vec::new();
+        let mut remaining_nodes = Vec::new();
+        let mut dismantle = |node, remaining_nodes: &mut Vec<_>| match node {
+            Node::Inner(_, _, _, _, children) => remaining_nodes.extend(children),
+            Node::Leaf(block) => blocks.push(block),
+        };
+        dismantle(
+            core::mem::replace(&mut self.root, Node::Leaf(Vec::new())),
+            &mut remaining_nodes,
+        );
+        while let Some(mut node) = remaining_nodes.pop() {
+            dismantle(
+                core::mem::replace(&mut *node, Node::Leaf(Vec::new())),
+                &mut remaining_nodes,
+            );
+            boxes.push(node);
+        }
+        (blocks, boxes)
+    }
+}
+
+/// Returns a seed for the generator of a new treap.
+#[cfg(feature = "std")]
+pub(super) fn fresh_seed() -> u64 {
+    rand::random()
+}
+
+/// Returns a seed for the generator of a new treap.
+/// Without `std` there is no entropy source, so seeds are scrambled ticks
+/// of a global counter : priorities stay random-looking and distinct treaps
+/// still get distinct generators.
+#[cfg(not(feature = "std"))]
+pub(super) fn fresh_seed() -> u64 {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    static TICKS: AtomicUsize = AtomicUsize::new(0);
+    let tick = TICKS.fetch_add(1, Ordering::Relaxed) as u64;
+    SmallRng::seed_from_u64(tick).gen()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Counts how many times equality was decided by comparing whole blocks.
+    pub(super) static BLOCK_COMPARISONS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    /// Counts how many times `between` allocated its stack of nodes.
+    pub(super) static STACK_ALLOCATIONS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+impl<C: core::fmt::Debug, const B: usize, M: Monoid<C>> core::fmt::Debug for ITreap<C, B, M> {
+    /// Formats the treap as the list of its elements, hiding the tree structure.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<C: PartialEq, const B: usize, M: Monoid<C>> PartialEq for ITreap<C, B, M> {
+    /// Two treaps are equal if they contain the same sequence of elements,
+    /// whatever their internal structure.
+    /// When both treaps share the same block boundaries (typically when built
+    /// by the same bulk operation) and the same pending reversals
+    /// we compare whole blocks as slices.
+    /// Cost is O(n).
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let layout = |root| {
+            oriented_blocks(root, false)
+                .map(|(block, reversed): (&[C], bool)| (block.len(), reversed))
+        };
+        if layout(&self.root).eq(layout(&other.root)) {
+            #[cfg(test)]
+            BLOCK_COMPARISONS.with(|c| c.set(c.get() + 1));
+            self.stored_blocks()
+                .zip(other.stored_blocks())
+                .all(|(b1, b2)| b1 == b2)
+        } else {
+            self.iter().eq(other.iter())
+        }
+    }
+}
+
+impl<C: Eq, const B: usize, M: Monoid<C>> Eq for ITreap<C, B, M> {}
+
+impl<C: core::hash::Hash, const B: usize, M: Monoid<C>> core::hash::Hash for ITreap<C, B, M> {
+    /// Hashes the length and then the element sequence, like vectors,
+    /// so that equal treaps hash identically whatever their internal structure.
+    /// Cost is O(n).
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        self.iter().for_each(|e| e.hash(state))
+    }
+}
+
+impl<C: PartialOrd, const B: usize, M: Monoid<C>> PartialOrd for ITreap<C, B, M> {
+    /// Compares element sequences lexicographically, like vectors.
+    /// Cost is O(n).
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<C: Ord, const B: usize, M: Monoid<C>> Ord for ITreap<C, B, M> {
+    /// Compares element sequences lexicographically, like vectors.
+    /// Cost is O(n).
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> core::iter::FromIterator<C> for ITreap<C, B, M> {
+    /// Transform an iterator into an indexed treap.
+    /// This will always create a perfectly balanced tree.
+    /// Cost is O(n).
+    fn from_iter<T: IntoIterator<Item = C>>(iter: T) -> Self {
+        let treap = ITreap::from_iter_unchecked(iter);
+        debug_assert!(treap.is_valid());
+        treap
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> From<Vec<C>> for ITreap<C, B, M> {
+    /// Builds a balanced indexed treap holding all elements of the vector.
+    /// Cost is O(n).
+    fn from(elements: Vec<C>) -> Self {
+        elements.into_iter().collect()
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> Extend<C> for ITreap<C, B, M> {
+    /// Adds all elements to the back.
+    /// Elements are gathered in a balanced tree which is then appended.
+    /// Cost is O(log(n/B)+B+k) where k is the number of new elements.
+    fn extend<I: IntoIterator<Item = C>>(&mut self, iter: I) {
+        self.append_blocks(
+            iter.into_iter()
+                .chunks(B / 2)
+                .into_iter()
+                .map(|chunk| chunk.collect()),
+        )
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> core::ops::Add for ITreap<C, B, M> {
+    type Output = Self;
+    /// Concatenates both treaps.
+    /// Cost is O(log(n/B)+B).
+    fn add(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+}
+
+impl<C, const B: usize, M: Monoid<C>> core::ops::AddAssign for ITreap<C, B, M> {
+    /// Adds all elements of `other` to the back.
+    /// Cost is O(log(n/B)+B).
+    fn add_assign(&mut self, mut other: Self) {
+        self.append(&mut other)
+    }
+}
+
+impl<'a, C: Copy + 'a, const B: usize, M: Monoid<C>> Extend<&'a C> for ITreap<C, B, M> {
+    /// Copies all elements to the back.
+    /// Cost is O(log(n/B)+B+k) where k is the number of new elements.
+    fn extend<I: IntoIterator<Item = &'a C>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+/// Iterator on the elements of a block, backwards or not.
+pub(super) type OrientedBlock<'a, C> =
+    itertools::Either<core::slice::Iter<'a, C>, core::iter::Rev<core::slice::Iter<'a, C>>>;
+
+/// Loops on the elements of given block, backwards if `reversed`.
+pub(super) fn oriented_iter<C>(block: &[C], reversed: bool) -> OrientedBlock<'_, C> {
+    if reversed {
+        itertools::Either::Right(block.iter().rev())
+    } else {
+        itertools::Either::Left(block.iter())
+    }
+}
+
+/// Loops on all leaf blocks below given node, in order (or in reverse order if `reversed`),
+/// telling for each one whether its elements should be read backwards.
+/// Cost is O(n/B).
+pub(super) fn oriented_blocks<C, const B: usize, M>(
+    root: &Node<C, B, M>,
+    reversed: bool,
+) -> impl Iterator<Item = (&[C], bool)> {
+    let mut remaining_nodes = vec![(root, reversed)];
+    core::iter::from_fn(move || {
+        while let Some((node, reversed)) = remaining_nodes.pop() {
+            match node {
+                Node::Inner(_, _, _, node_reversed, [left, right]) => {
+                    let reversed = reversed ^ node_reversed;
+                    if reversed {
+                        remaining_nodes.push((left, reversed));
+                        remaining_nodes.push((right, reversed));
+                    } else {
+                        remaining_nodes.push((right, reversed));
+                        remaining_nodes.push((left, reversed));
+                    }
+                }
+                Node::Leaf(block) => return Some((block.as_slice(), reversed)),
+            }
+        }
+        None
+    })
+}
+
+/// Moves out all leaf blocks below given node, in order.
+/// Cost is O(n/B).
+fn node_blocks<C, const B: usize, M>(root: Node<C, B, M>) -> impl Iterator<Item = Vec<C>> {
+    let mut remaining_nodes = vec![root];
+    core::iter::from_fn(move || {
+        while let Some(mut node) = remaining_nodes.pop() {
+            node.push_down();
+            match node {
+                Node::Inner(_, _, _, _, [left, right]) => {
+                    remaining_nodes.push(*right);
+                    remaining_nodes.push(*left);
+                }
+                Node::Leaf(block) => return Some(block),
+            }
+        }
+        None
+    })
+}
+
+/// Builds a perfectly balanced tree whose leaves are the given (non-empty) blocks.
+/// Cost is O(number of blocks).
+fn build_from_blocks<C, const B: usize, M: Monoid<C>, I: Iterator<Item = Vec<C>>>(
+    blocks: I,
+    rng: &mut SmallRng,
+) -> Node<C, B, M> {
+    build_reusing_boxes(blocks, rng, &mut Vec::new())
+}
+
+/// Builds a perfectly balanced tree whose leaves are the given (non-empty) blocks,
+/// taking node boxes from the given spare ones before allocating new ones.
+/// Cost is O(number of blocks).
+fn build_reusing_boxes<C, const B: usize, M: Monoid<C>, I: Iterator<Item = Vec<C>>>(
+    blocks: I,
+    rng: &mut SmallRng,
+    spare_boxes: &mut SpareBoxes<C, B, M>,
+) -> Node<C, B, M> {
+    let mut boxed = |node| match spare_boxes.pop() {
+        Some(mut spare) => {
+            *spare = node;
+            spare
+        }
+        None => Box::new(node),
+    };
+    let (mut tree, leaves) = blocks.fold((Vec::new(), 0), |(mut tree, leaves), block| {
+        // we keep a stack of nodes
+        // and merge the last two nodes when the get equal size
+        tree.push(boxed(Node::Leaf(block)));
+        loop {
+            let l = tree.len();
+            if l >= 2 && tree[l - 1].len() == tree[l - 2].len() {
+                let right_node = tree.pop().unwrap();
+                let left_node = tree.pop().unwrap();
+                // let's have a fake priority, we'll set it later
+                let merged = Node::inner(0, [left_node, right_node]);
+                tree.push(boxed(merged));
+            } else {
+                break;
+            }
+        }
+        (tree, leaves + 1)
+    });
+    let right_node = tree.pop();
+    if let Some(mut right_node) = right_node {
+        // build the treap
+        while let Some(left_node) = tree.pop() {
+            right_node = boxed(Node::inner(0, [left_node, right_node]));
+        }
+        let mut root = *right_node;
+        // now, fix priorities
+        let mut priorities: Vec<Priority> = core::iter::repeat_with(|| rng.gen())
+            .take(leaves - 1)
+            .collect();
+        priorities.sort_unstable();
+        for_each_node_breadth_first(&mut root, |node| {
+            if let Node::Inner(priority, _, _, _, _) = node {
+                *priority = priorities.pop().unwrap()
+            }
+        });
+        root
+    } else {
+        Node::Leaf(Vec::new())
+    }
 }
 
-fn for_each_node_breadth_first<C, F: FnMut(&mut Node<C>)>(root: &mut Node<C>, mut op: F) {
-    let mut remaining: std::collections::VecDeque<_> = std::iter::once(root).collect();
+fn for_each_node_breadth_first<C, const B: usize, M, F: FnMut(&mut Node<C, B, M>)>(
+    root: &mut Node<C, B, M>,
+    mut op: F,
+) {
+    let mut remaining: alloc::collections::VecDeque<_> = core::iter::once(root).collect();
     while let Some(node) = remaining.pop_front() {
         op(node);
-        match node {
-            Node::Inner(_, _, children) => remaining.extend(children.iter_mut().map(|b| &mut **b)),
-            _ => (),
+        if let Node::Inner(_, _, _, _, children) = node {
+            remaining.extend(children.iter_mut().map(|b| &mut **b))
         }
     }
 }
 
-fn intersect_ranges(r1: &Range<usize>, r2: &Range<usize>) -> Range<usize> {
+pub(super) fn intersect_ranges(r1: &Range<usize>, r2: &Range<usize>) -> Range<usize> {
     r1.start.max(r2.start)..r1.end.min(r2.end)
 }
@@ -0,0 +1,61 @@
+use super::{ITreap, Node, TotalWeight, Weight, LEFT, RIGHT};
+
+impl<C: Weight, const B: usize> ITreap<C, B, TotalWeight> {
+    /// Returns the sum of the weights of all elements.
+    /// Cost is O(B).
+    pub fn total_weight(&self) -> u64 {
+        self.root.summary().0
+    }
+    /// Finds the element covering given weight offset,
+    /// that is the first one for which the total weight of all elements up to it
+    /// (itself included) is above `w`, returning it with its index.
+    /// Returns `None` if `w` is not below the total weight.
+    /// Cost is O(log(n/B)+B).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use itreap::{Weight, WeightedTreap};
+    ///
+    /// struct Task(u64);
+    /// impl Weight for Task {
+    ///     fn weight(&self) -> u64 {
+    ///         self.0
+    ///     }
+    /// }
+    ///
+    /// let t: WeightedTreap<_> = vec![Task(3), Task(0), Task(2)].into_iter().collect();
+    /// assert_eq!(t.select_by_weight(2).map(|(i, _)| i), Some(0));
+    /// assert_eq!(t.select_by_weight(3).map(|(i, _)| i), Some(2));
+    /// assert!(t.select_by_weight(5).is_none());
+    /// ```
+    pub fn select_by_weight(&self, w: u64) -> Option<(usize, &C)> {
+        let mut node = &self.root;
+        let mut index = 0;
+        let mut before = 0;
+        loop {
+            match node {
+                Node::Leaf(block) => {
+                    return block.iter().enumerate().find_map(|(i, e)| {
+                        before += e.weight();
+                        if before > w {
+                            Some((index + i, e))
+                        } else {
+                            None
+                        }
+                    })
+                }
+                Node::Inner(_, _, _, _, children) => {
+                    let with_left = before + children[LEFT].summary().0;
+                    if with_left > w {
+                        node = &children[LEFT];
+                    } else {
+                        before = with_left;
+                        index += children[LEFT].len();
+                        node = &children[RIGHT];
+                    }
+                }
+            }
+        }
+    }
+}